@@ -0,0 +1,21 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use unblend::blend::*;
+use unblend::read_ext::ReadExt;
+
+fuzz_target!(|data: &[u8]| {
+    let mut read = std::io::Cursor::new(data);
+    
+    let Ok(blend) = read_header(&mut read) else {
+        return
+    };
+    
+    while let Ok(chunk_head) = read_chunk_header(&blend, &mut read) {
+        let _ = format!("{chunk_head}");
+        
+        if std::io::copy(&mut read.take_borrowed(chunk_head.size as usize), &mut std::io::sink()).is_err() {
+            break;
+        }
+    }
+});
@@ -0,0 +1,40 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use unblend::blend::*;
+use unblend::output::Output;
+
+/// Discards everything, so only the parser is exercised.
+struct OutputToSink;
+impl Output for OutputToSink {
+    fn write_file(
+        &mut self,
+        _path: &str,
+        _size: u64,
+        data: &mut dyn std::io::Read
+    ) -> std::io::Result<()> {
+        std::io::copy(data, &mut std::io::sink()).map(|_|())
+    }
+    
+    fn finish(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    // The first byte picks the header the DNA1 block is read with.
+    let Some((&flags, dna1)) = data.split_first() else {
+        return
+    };
+    
+    let blend = BlendHeader {
+        layout: BlendHeaderLayout::Legacy,
+        usize: if flags & 1 == 0 {BlendUsize::U32} else {BlendUsize::U64},
+        endian: if flags & 2 == 0 {BlendEndian::LE} else {BlendEndian::BE},
+        version: BlendVersion { major: 3, minor: 0 },
+    };
+    
+    if let Ok(dna) = read_dna1(&blend, dna1) {
+        let _ = write_dna1(&blend, &dna, &mut OutputToSink);
+    }
+});
@@ -2,43 +2,95 @@ use std::{io::Read, ops::Deref};
 use align_address::Align;
 
 use crate::read_ext::ReadExt;
+use crate::error::UnblendError;
+use crate::sdna::*;
+use crate::codegen::{CodegenLang, write_code};
 
-pub fn read_header(read: &mut impl Read) -> std::io::Result<BlendHeader> {
-    eprintln!("Beginning parsing of blend-file.");
+pub fn read_header(read: &mut impl Read) -> Result<BlendHeader, UnblendError> {
+    log::info!("Beginning parsing of blend-file.");
     
+    let mut read = Recorded { read, bytes: Vec::new() };
+    
+    match read_header_fields(&mut read) {
+        Err(UnblendError::Io(error)) if error.kind() == std::io::ErrorKind::UnexpectedEof => {
+            Err(UnblendError::BadHeader { field: "length", bytes: read.bytes })
+        },
+        result => result,
+    }
+}
+
+/// Keeps every byte read, so that a header cut short can be shown.
+struct Recorded<'r, R> {
+    read: &'r mut R,
+    bytes: Vec<u8>,
+}
+
+impl<R: Read> Read for Recorded<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.read.read(buf)?;
+        self.bytes.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+}
+
+fn read_header_fields(read: &mut impl Read) -> Result<BlendHeader, UnblendError> {
     let blend_magic = read.read_exact_array::<7>()?;
     
     if &blend_magic != b"BLENDER" {
-        panic!("File is not a blend-file: Magic bytes dont match `BLENDER`");
+        return Err(UnblendError::BadMagic(blend_magic));
     }
     
-    let blend_usize = match read.read_byte()? {
-        b'_' => BlendUsize::U32,
-        b'-' => BlendUsize::U64,
-        b => panic!("Blend-file header has invalid pointer-size: {b:?}")
+    let (blend_layout, blend_usize) = match read.read_byte()? {
+        b'_' => (BlendHeaderLayout::Legacy, BlendUsize::U32),
+        b'-' => (BlendHeaderLayout::Legacy, BlendUsize::U64),
+        b if b.is_ascii_digit() => {
+            let header_size = [b, read.read_byte()?];
+            
+            if read_ascii_number(&header_size) != Some(17) {
+                return Err(UnblendError::BadHeader { field: "header-size", bytes: header_size.to_vec() });
+            }
+            
+            let separator = read.read_byte()?;
+            
+            if separator != b'-' {
+                return Err(UnblendError::BadHeader { field: "separator", bytes: vec![separator] });
+            }
+            
+            let format_version = read.read_exact_array::<2>()?;
+            let format_version = match read_ascii_number(&format_version) {
+                Some(number) => number as u8,
+                None => return Err(UnblendError::BadVersion(format_version.to_vec())),
+            };
+            
+            // The large header always comes with 64-bit pointers.
+            (BlendHeaderLayout::Large { format_version }, BlendUsize::U64)
+        },
+        b => return Err(UnblendError::BadPointerSize(b))
     };
     
     let blend_endian = match read.read_byte()? {
         b'v' => BlendEndian::LE,
         b'V' => BlendEndian::BE,
-        b => panic!("Blend-file header has invalid endianess: {b:?}")
+        b => return Err(UnblendError::BadEndianness(b))
     };
     
-    let blend_version = read.read_exact_array::<3>()?;
-    
-    if ! blend_version.iter().all(|b| b.is_ascii_digit()) {
-        panic!("Blend-file header has invalid version: {blend_version:?}");
-    }
+    let blend_version = match blend_layout {
+        BlendHeaderLayout::Legacy => read.read_exact_array::<3>()?.to_vec(),
+        BlendHeaderLayout::Large { .. } => read.read_exact_array::<4>()?.to_vec(),
+    };
     
-    let blend_version = BlendVersion {
-        major: blend_version[0],
-        minor: blend_version[1],
-        patch: blend_version[2]
+    let blend_version = match read_ascii_number(&blend_version) {
+        Some(version) => BlendVersion {
+            major: (version / 100) as u8,
+            minor: (version % 100) as u8,
+        },
+        None => return Err(UnblendError::BadVersion(blend_version))
     };
     
-    eprintln!("Parsed header: usize={blend_usize:?}, endian={blend_endian:?}, version={blend_version}");
+    log::info!("Parsed header: layout={blend_layout}, usize={blend_usize:?}, endian={blend_endian:?}, version={blend_version}");
     
     let blend_header = BlendHeader {
+        layout: blend_layout,
         usize: blend_usize,
         endian: blend_endian,
         version: blend_version,
@@ -47,11 +99,33 @@ pub fn read_header(read: &mut impl Read) -> std::io::Result<BlendHeader> {
     Ok(blend_header)
 }
 
+/// Parses a fixed-width number made of ASCII digits.
+fn read_ascii_number(digits: &[u8]) -> Option<u16> {
+    digits.iter().try_fold(0u16, |number, digit| {
+        digit.is_ascii_digit().then(|| number * 10 + (digit - b'0') as u16)
+    })
+}
+
 pub fn read_chunk_header(blend: &BlendHeader, read: &mut impl Read) -> std::io::Result<BlendChunkHeader> {
     
     let chunk_code = BlendChunkCode(read.read_exact_array::<4>()?);
     
-    let chunk_size = blend.endian.read_u32(read)?;
+    if let BlendHeaderLayout::Large { .. } = blend.layout {
+        let chunk_sdna = blend.endian.read_u32(read)?;
+        let chunk_addr = blend.endian.read_u64(read)?;
+        let chunk_size = blend.endian.read_u64(read)?;
+        let chunk_count = blend.endian.read_u64(read)?;
+        
+        return Ok(BlendChunkHeader {
+            code: chunk_code,
+            size: chunk_size,
+            addr: chunk_addr,
+            sdna: chunk_sdna,
+            count: chunk_count
+        })
+    }
+    
+    let chunk_size = blend.endian.read_u32(read)?.into();
     
     let chunk_addr: u64 = match blend.usize {
         BlendUsize::U32 => blend.endian.read_u32(read)?.into(),
@@ -60,7 +134,7 @@ pub fn read_chunk_header(blend: &BlendHeader, read: &mut impl Read) -> std::io::
     
     let chunk_sdna = blend.endian.read_u32(read)?;
     
-    let chunk_count = blend.endian.read_u32(read)?;
+    let chunk_count = blend.endian.read_u32(read)?.into();
     
     Ok(BlendChunkHeader {
         code: chunk_code,
@@ -71,31 +145,94 @@ pub fn read_chunk_header(blend: &BlendHeader, read: &mut impl Read) -> std::io::
     })
 }
 
-pub fn read_dna1(blend: &BlendHeader, dna1: Vec<u8>, output: &mut dyn crate::Output) -> std::io::Result<()> {
+/// Writes the file-header, as [`read_header`] would read it.
+pub fn write_header(blend: &BlendHeader, write: &mut (impl std::io::Write + ?Sized)) -> std::io::Result<()> {
+    let version = blend.version.major as u16 * 100 + blend.version.minor as u16;
+    
+    match blend.layout {
+        BlendHeaderLayout::Legacy => write!(write, "BLENDER{}{}{version:03}", blend.usize as u8 as char, blend.endian as u8 as char),
+        BlendHeaderLayout::Large { format_version } => write!(write, "BLENDER{:02}-{format_version:02}{}{version:04}", blend.layout.header_len(), blend.endian as u8 as char),
+    }
+}
+
+/// Writes a block-header, as [`read_chunk_header`] would read it.
+/// 
+/// Fails if a value doesn't fit the (legacy) block-header.
+pub fn write_chunk_header(blend: &BlendHeader, chunk: &BlendChunkHeader, write: &mut (impl std::io::Write + ?Sized)) -> std::io::Result<()> {
+    let endian = blend.endian;
+    write.write_all(&chunk.code.0)?;
+    
+    if let BlendHeaderLayout::Large { .. } = blend.layout {
+        write.write_all(&endian.u32_bytes(chunk.sdna))?;
+        write.write_all(&endian.u64_bytes(chunk.addr))?;
+        write.write_all(&endian.u64_bytes(chunk.size))?;
+        write.write_all(&endian.u64_bytes(chunk.count))?;
+        return Ok(())
+    }
+    
+    let narrow = |value: u64, what: &str| u32::try_from(value).map_err(|_| std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!("block `{}` has a {what} of 0x{value:X?}, which doesn't fit the block-header", chunk.code)
+    ));
+    
+    write.write_all(&endian.u32_bytes(narrow(chunk.size, "size")?))?;
+    
+    match blend.usize {
+        BlendUsize::U32 => write.write_all(&endian.u32_bytes(narrow(chunk.addr, "address")?))?,
+        BlendUsize::U64 => write.write_all(&endian.u64_bytes(chunk.addr))?,
+    }
+    
+    write.write_all(&endian.u32_bytes(chunk.sdna))?;
+    write.write_all(&endian.u32_bytes(narrow(chunk.count, "count")?))?;
+    Ok(())
+}
+
+/// Parses the payload of the `DNA1` block.
+pub fn read_dna1(blend: &BlendHeader, dna1: &[u8]) -> Result<Sdna, UnblendError> {
     
     
     use crate::byte_ext::*;
     let endian = blend.endian;
     
-    ///////////////////////////////////////////////////////
+    fn read_u16(endian: BlendEndian, dna1: &[u8], from: usize) -> Result<u16, UnblendError> {
+        match copy_at::<2>(dna1, from) {
+            Some(bytes) => Ok(endian.u16(bytes)),
+            None => Err(UnblendError::malformed_dna(from, "unexpected end of DNA1 while reading a short")),
+        }
+    }
     
-    let from = 0;
+    fn read_u32(endian: BlendEndian, dna1: &[u8], from: usize) -> Result<u32, UnblendError> {
+        match copy_at::<4>(dna1, from) {
+            Some(bytes) => Ok(endian.u32(bytes)),
+            None => Err(UnblendError::malformed_dna(from, "unexpected end of DNA1 while reading an int")),
+        }
+    }
     
-    if ! dna1[from..].starts_with(b"SDNA") {
-        panic!("DNA1 at {from:X?} does not start with the required magic string `SDNA`");
+    fn expect_magic(dna1: &[u8], from: usize, what: &str, magic: &[u8; 4]) -> Result<usize, UnblendError> {
+        if ! dna1.get(from..).is_some_and(|rest| rest.starts_with(magic)) {
+            let magic = magic.escape_ascii();
+            return Err(UnblendError::malformed_dna(from, format!("{what} does not start with the required magic string `{magic}`")));
+        }
+        
+        Ok(from + 4)
     }
     
-    let from = from + 4;
+    ///////////////////////////////////////////////////////
+    
+    let from = expect_magic(dna1, 0, "DNA1", b"SDNA")?;
     
     ///////////////////////////////////////////////////////
     
-    fn read_cstr_list(endian: BlendEndian, dna1: &[u8], from: usize) -> (usize, Vec<&std::ffi::CStr>) {
-        let names_len = endian.u32(copy::<4>(&dna1[from..]));
-        let mut names = Vec::with_capacity(names_len as usize);
+    fn read_cstr_list(endian: BlendEndian, dna1: &[u8], from: usize) -> Result<(usize, Vec<&std::ffi::CStr>), UnblendError> {
+        let names_len = read_u32(endian, dna1, from)?;
+        // Every name takes at least one byte, which bounds the capacity for crafted lengths.
+        let mut names = Vec::with_capacity((names_len as usize).min(dna1.len()));
         
         let mut from = from + 4;
         for _ in 0..names_len {
-            let name = std::ffi::CStr::from_bytes_until_nul(&dna1[from..]).unwrap();
+            let name = dna1.get(from..)
+                .and_then(|rest| std::ffi::CStr::from_bytes_until_nul(rest).ok())
+                .ok_or_else(|| UnblendError::malformed_dna(from, "string is not nul-terminated"))?;
             let size = name.to_bytes_with_nul().len();
             //eprintln!("Read CStr [{from:X?}+{size}]: {name:?}");
             from += size;
@@ -106,39 +243,27 @@ pub fn read_dna1(blend: &BlendHeader, dna1: Vec<u8>, output: &mut dyn crate::Out
         
         //eprintln!("Read {}/{}: from={from:X?}", names.len(), names_len);
         
-        (from, names)
+        Ok((from, names))
     }
     
     ///////////////////////////////////////////////////////
     
-    if ! dna1[from..].starts_with(b"NAME") {
-        panic!("NAME-list at {from:X?} does not start with the required magic string `NAME`");
-    }
-    
-    let from = from + 4;
-    let (from, names) = read_cstr_list(endian, &dna1, from);
+    let from = expect_magic(dna1, from, "NAME-list", b"NAME")?;
+    let (from, names) = read_cstr_list(endian, dna1, from)?;
     
     ///////////////////////////////////////////////////////
     
-    if ! dna1[from..].starts_with(b"TYPE") {
-        panic!("TYPE-list at {from:X?} does not start with the required magic string `TYPE`");
-    }
-    
-    let from = from + 4;
-    let (from, types) = read_cstr_list(endian, &dna1, from);
+    let from = expect_magic(dna1, from, "TYPE-list", b"TYPE")?;
+    let (from, types) = read_cstr_list(endian, dna1, from)?;
     
     ///////////////////////////////////////////////////////
     
-    if ! dna1[from..].starts_with(b"TLEN") {
-        panic!("TLEN-list at {from:X?} does not start with the required magic string `TLEN`");
-    }
-    
-    let from = from + 4;
+    let from = expect_magic(dna1, from, "TLEN-list", b"TLEN")?;
     let (from, lengths) = {
         let mut lengths = Vec::<u16>::with_capacity(types.len());
         let mut from = from;
         for _ in 0..types.len() {
-            let length = endian.u16(copy::<2>(&dna1[from..])); from += 2;
+            let length = read_u16(endian, dna1, from)?; from += 2;
             lengths.push(length);
         }
         
@@ -151,21 +276,26 @@ pub fn read_dna1(blend: &BlendHeader, dna1: Vec<u8>, output: &mut dyn crate::Out
     
     ///////////////////////////////////////////////////////
     
-    if ! dna1[from..].starts_with(b"STRC") {
-        panic!("STRC-list at {from:X?} does not start with the required magic string `STRC`");
-    }
-    
-    let from = from + 4;
+    let from = expect_magic(dna1, from, "STRC-list", b"STRC")?;
     
-    let structs_len = endian.u32(copy::<4>(&dna1[from..]));
-    let mut structs = Vec::<(u16, _)>::with_capacity(structs_len as usize);
-    let mut from = from;
+    let structs_len = read_u32(endian, dna1, from)?;
+    // Every struct takes at least four bytes, which bounds the capacity for crafted lengths.
+    let mut structs = Vec::<(u16, _)>::with_capacity((structs_len as usize).min(dna1.len() / 4));
+    let mut offsets = Vec::with_capacity(structs.capacity());
+    let mut from = from + 4;
     
     //eprintln!("@{from:X?} structs[{structs_len}] start");
     
     for _ in 0..structs_len {
-        let structtype = endian.u16(copy::<2>(&dna1[from..])); from += 2;
-        let fields_len = endian.u16(copy::<2>(&dna1[from..])); from += 2;
+        let structtype = read_u16(endian, dna1, from)?;
+        
+        if structtype as usize >= types.len() {
+            return Err(UnblendError::malformed_dna(from, format!("struct has out-of-range type index {structtype}")));
+        }
+        
+        from += 2;
+        let fields_len = read_u16(endian, dna1, from)?; from += 2;
+        offsets.push(from);
         
         //let structname = types[structtype as usize];
         //eprintln!("@{from:X?} struct {structname:?} #{fields_len} start");
@@ -173,8 +303,20 @@ pub fn read_dna1(blend: &BlendHeader, dna1: Vec<u8>, output: &mut dyn crate::Out
         let mut fields = Vec::<(u16, u16)>::with_capacity(fields_len as usize);
         
         for _ in 0..fields_len {
-            let ftype = endian.u16(copy::<2>(&dna1[from..])); from += 2;
-            let fname = endian.u16(copy::<2>(&dna1[from..])); from += 2;
+            let ftype = read_u16(endian, dna1, from)?;
+            
+            if ftype as usize >= types.len() {
+                return Err(UnblendError::malformed_dna(from, format!("field has out-of-range type index {ftype}")));
+            }
+            
+            from += 2;
+            let fname = read_u16(endian, dna1, from)?;
+            
+            if fname as usize >= names.len() {
+                return Err(UnblendError::malformed_dna(from, format!("field has out-of-range name index {fname}")));
+            }
+            
+            from += 2;
             fields.push((ftype, fname));
             
             //eprintln!("@{from:X?} struct {structname:?} #{fields_len} field {:?} of type {:?}", names[fname as usize], types[ftype as usize]);
@@ -187,6 +329,115 @@ pub fn read_dna1(blend: &BlendHeader, dna1: Vec<u8>, output: &mut dyn crate::Out
     
     ///////////////////////////////////////////////////////
     
+    let to_strings = |cstrs: Vec<&std::ffi::CStr>| cstrs.into_iter()
+        .map(|cstr| cstr.to_string_lossy().into_owned())
+        .collect();
+    
+    let sdna = Sdna::with_offsets(to_strings(names), to_strings(types), lengths, structs, &offsets, blend.usize.len() as usize)?;
+    
+    for strct in &sdna.structs {
+        if strct.fields_size() != strct.size as usize {
+            log::warn!("struct `{}` has fields of {} byte/s, but a size of {} byte/s", strct.name, strct.fields_size(), strct.size);
+        }
+    }
+    
+    Ok(sdna)
+}
+
+/// Encodes the SDNA as the payload of a `DNA1` block, as [`read_dna1`] would read it.
+pub fn encode_dna1(endian: BlendEndian, sdna: &Sdna) -> Vec<u8> {
+    fn pad(dna1: &mut Vec<u8>) {
+        dna1.resize(dna1.len().align_up(4usize), 0);
+    }
+    
+    let mut dna1 = Vec::from(*b"SDNA");
+    
+    dna1.extend_from_slice(b"NAME");
+    dna1.extend_from_slice(&endian.u32_bytes(sdna.names.len() as u32));
+    for name in &sdna.names {
+        dna1.extend_from_slice(name.as_bytes());
+        dna1.push(0);
+    }
+    pad(&mut dna1);
+    
+    dna1.extend_from_slice(b"TYPE");
+    dna1.extend_from_slice(&endian.u32_bytes(sdna.types.len() as u32));
+    for stype in &sdna.types {
+        dna1.extend_from_slice(stype.name.as_bytes());
+        dna1.push(0);
+    }
+    pad(&mut dna1);
+    
+    dna1.extend_from_slice(b"TLEN");
+    for stype in &sdna.types {
+        dna1.extend_from_slice(&endian.u16_bytes(stype.size));
+    }
+    pad(&mut dna1);
+    
+    dna1.extend_from_slice(b"STRC");
+    dna1.extend_from_slice(&endian.u32_bytes(sdna.structs.len() as u32));
+    for strct in &sdna.structs {
+        dna1.extend_from_slice(&endian.u16_bytes(strct.type_index));
+        dna1.extend_from_slice(&endian.u16_bytes(strct.fields.len() as u16));
+        for field in &strct.fields {
+            dna1.extend_from_slice(&endian.u16_bytes(field.type_index));
+            dna1.extend_from_slice(&endian.u16_bytes(field.name_index));
+        }
+    }
+    
+    dna1
+}
+
+/// The version of the `DNA1.json` schema; incremented whenever existing keys change their meaning.
+pub const DNA_JSON_VERSION: u32 = 1;
+
+/// Describes the SDNA as JSON, as written to `DNA1.json`.
+///
+/// Keys and their order are stable; structs are listed by SDNA index, fields by offset.
+pub fn dna_to_json(blend: &BlendHeader, sdna: &Sdna) -> serde_json::Value {
+    let primitives: Vec<_> = sdna.types.iter()
+        .enumerate()
+        .filter(|(type_index, _)| sdna.struct_by_type(*type_index as u16).is_none())
+        .map(|(_, stype)| serde_json::json!({
+            "name": stype.name,
+            "size": stype.size,
+        }))
+        .collect();
+    
+    let structs: Vec<_> = sdna.structs.iter()
+        .map(|strct| serde_json::json!({
+            "name": strct.name,
+            "sdna": strct.sdna,
+            "size": strct.size,
+            "fields": strct.fields.iter()
+                .map(|field| serde_json::json!({
+                    "name": field.parsed.ident,
+                    "dna_name": field.name,
+                    "type": field.type_name,
+                    "pointer_depth": field.parsed.pointer_depth,
+                    "function_pointer": field.parsed.is_function_pointer,
+                    "array_dims": field.parsed.array_dims,
+                    "offset": field.offset,
+                    "size": field.size,
+                }))
+                .collect::<Vec<_>>(),
+        }))
+        .collect();
+    
+    serde_json::json!({
+        "version": DNA_JSON_VERSION,
+        "blender": blend.version.to_string(),
+        "pointer_size": blend.usize.len(),
+        "endian": format!("{:?}", blend.endian),
+        "primitives": primitives,
+        "structs": structs,
+    })
+}
+
+/// Writes the `DNA1.tsv` index and the per-struct `DNA1/*.txt` files,
+/// along with the whole SDNA as `DNA1.json`,
+/// the structs as `DNA1.h` C header and the file layout as `DNA1.ksy` Kaitai description.
+pub fn write_dna1(blend: &BlendHeader, sdna: &Sdna, output: &mut dyn crate::output::Output) -> Result<(), UnblendError> {
     use std::fmt::Write;
     let mut index = String::default();
     writeln!(&mut index, "sdna\tsize\tpath").unwrap();
@@ -199,19 +450,17 @@ pub fn read_dna1(blend: &BlendHeader, dna1: Vec<u8>, output: &mut dyn crate::Out
     writeln!(&mut index, "-\t0x8\tbuiltin:double").unwrap();
     writeln!(&mut index, "-\t0x{:X?}\tbuiltin:void", blend.usize.len()).unwrap();
     
-    for (sdna, (stype, fields)) in structs.iter().enumerate() {
-        let ssize = lengths[*stype as usize];
-        let sname = types[*stype as usize].to_string_lossy();
+    for strct in &sdna.structs {
+        let SdnaStruct { sdna, type_index: stype, name: sname, size: ssize, fields } = strct;
         
         let mut buffer = String::default();
         writeln!(&mut buffer, "# name {sname} @{stype}").unwrap();
         writeln!(&mut buffer, "# size {ssize}").unwrap();
         writeln!(&mut buffer, "# fields {}", fields.len()).unwrap();
         
-        for (ftype, fname) in fields {
-            let fname = names[*fname as usize].to_string_lossy();
-            let ftype = types[*ftype as usize].to_string_lossy();
-            writeln!(&mut buffer, "{fname}\t{ftype}").unwrap();
+        for field in fields {
+            let ftype = field.parsed.type_string(&field.type_name);
+            writeln!(&mut buffer, "0x{:X?}\t0x{:X?}\t{}\t{ftype}", field.offset, field.size, field.parsed.ident).unwrap();
         }
         
         let path = format!("DNA1/{sname}.txt");
@@ -222,7 +471,7 @@ pub fn read_dna1(blend: &BlendHeader, dna1: Vec<u8>, output: &mut dyn crate::Out
             &mut std::io::Cursor::new(&buffer)
         ) {
             Ok(()) => (),
-            Err(error) => eprintln!("ERROR while writing `{path}`: {error}"),
+            Err(error) => log::error!("Failed to write `{path}`: {error}"),
         };
         
         writeln!(&mut index, "0x{sdna:X?}\t0x{ssize:X?}\t{path}").unwrap();
@@ -234,17 +483,39 @@ pub fn read_dna1(blend: &BlendHeader, dna1: Vec<u8>, output: &mut dyn crate::Out
         &mut std::io::Cursor::new(&index)
     )?;
     
+    let json = serde_json::to_string_pretty(&dna_to_json(blend, sdna)).unwrap();
+    
+    output.write_file(
+        "DNA1.json",
+        json.len() as u64,
+        &mut std::io::Cursor::new(&json)
+    )?;
+    
+    for (path, lang) in [("DNA1.h", CodegenLang::C), ("DNA1.ksy", CodegenLang::Kaitai)] {
+        let mut buffer = Vec::new();
+        
+        match write_code(lang, blend, sdna, &mut buffer).and_then(|()| output.write_file(
+            path,
+            buffer.len() as u64,
+            &mut std::io::Cursor::new(&buffer)
+        )) {
+            Ok(()) => (),
+            Err(error) => log::error!("Failed to write `{path}`: {error}"),
+        };
+    }
+    
     Ok(())
 }
 
 #[repr(u8)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BlendUsize {
     U32 = b'_',
     U64 = b'-',
 }
 
 impl BlendUsize {
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(self) -> u8 {
         match self {
             BlendUsize::U32 => 4,
@@ -254,7 +525,7 @@ impl BlendUsize {
 }
 
 #[repr(u8)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BlendEndian {
     LE = b'v',
     BE = b'V',
@@ -289,45 +560,115 @@ impl BlendEndian {
         }
     }
     
-    // pub fn u64(self, buf: [u8; 8]) -> u64 {
-    //     match self {
-    //         BlendEndian::LE => u64::from_le_bytes(buf),
-    //         BlendEndian::BE => u64::from_be_bytes(buf),
-    //     }
-    // }
+    pub fn u64(self, buf: [u8; 8]) -> u64 {
+        match self {
+            BlendEndian::LE => u64::from_le_bytes(buf),
+            BlendEndian::BE => u64::from_be_bytes(buf),
+        }
+    }
+    
+    pub fn u16_bytes(self, value: u16) -> [u8; 2] {
+        match self {
+            BlendEndian::LE => value.to_le_bytes(),
+            BlendEndian::BE => value.to_be_bytes(),
+        }
+    }
+    
+    pub fn u32_bytes(self, value: u32) -> [u8; 4] {
+        match self {
+            BlendEndian::LE => value.to_le_bytes(),
+            BlendEndian::BE => value.to_be_bytes(),
+        }
+    }
+    
+    pub fn u64_bytes(self, value: u64) -> [u8; 8] {
+        match self {
+            BlendEndian::LE => value.to_le_bytes(),
+            BlendEndian::BE => value.to_be_bytes(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct BlendVersion {
     pub major: u8,
     pub minor: u8,
-    pub patch: u8,
+}
+
+/// Which of the file-header layouts a blend-file uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendHeaderLayout {
+    /// The 12-byte header with 32-bit block sizes, like `BLENDER-v300`.
+    Legacy,
+    /// The 17-byte header with 64-bit block sizes, like `BLENDER17-01v0500`, used since Blender 5.0.
+    Large {
+        format_version: u8,
+    },
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct BlendHeader {
+    pub layout: BlendHeaderLayout,
     pub usize: BlendUsize,
     pub endian: BlendEndian,
     pub version: BlendVersion,
 }
 
+impl BlendHeader {
+    /// The length of a block-header in this file.
+    pub fn chunk_header_len(&self) -> u64 {
+        match self.layout {
+            BlendHeaderLayout::Legacy => 16 + self.usize.len() as u64,
+            BlendHeaderLayout::Large { .. } => 32,
+        }
+    }
+}
+
+impl BlendHeaderLayout {
+    /// The length of the file-header in this layout.
+    pub fn header_len(self) -> u64 {
+        match self {
+            BlendHeaderLayout::Legacy => 12,
+            BlendHeaderLayout::Large { .. } => 17,
+        }
+    }
+}
+
 impl std::fmt::Display for BlendHeader {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "usize\t{:?}\nendian\t{:?}\nversion\t{}\n", self.usize, self.endian, self.version)
+        write!(f, "layout\t{}\nusize\t{:?}\nendian\t{:?}\nversion\t{}\n", self.layout, self.usize, self.endian, self.version)
+    }
+}
+
+impl std::fmt::Display for BlendHeaderLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlendHeaderLayout::Legacy => write!(f, "Legacy"),
+            BlendHeaderLayout::Large { format_version } => write!(f, "Large-{format_version:02}"),
+        }
     }
 }
 
 impl std::fmt::Display for BlendVersion {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}.{}{}", self.major as char, self.minor as char, self.patch as char)
+        write!(f, "{}.{:02}", self.major, self.minor)
     }
 }
 
-pub struct BlendChunkCode([u8;4]);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlendChunkCode(pub [u8;4]);
+
+impl BlendChunkCode {
+    /// Whether this is the two-letter code of an ID datablock, like `OB` or `ME`,
+    /// whose payload starts with the `ID` struct.
+    pub fn is_id(&self) -> bool {
+        self.0[0] != 0 && self.0[1] != 0 && self.0[2] == 0 && self.0[3] == 0
+    }
+}
 
 impl std::ops::Deref for BlendChunkCode {
     type Target = [u8];
-
+    
     fn deref(&self) -> &Self::Target {
         &self.0
     }
@@ -339,6 +680,12 @@ impl PartialEq<&[u8]> for BlendChunkCode {
     }
 }
 
+impl PartialEq<&[u8; 4]> for BlendChunkCode {
+    fn eq(&self, other: &&[u8; 4]) -> bool {
+        &self.0 == *other
+    }
+}
+
 impl std::fmt::Display for BlendChunkCode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for byte in self.deref() {
@@ -350,12 +697,13 @@ impl std::fmt::Display for BlendChunkCode {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
 pub struct BlendChunkHeader {
     pub code: BlendChunkCode,
-    pub size: u32,
+    pub size: u64,
     pub addr: u64,
     pub sdna: u32,
-    pub count: u32
+    pub count: u64
 }
 
 impl std::fmt::Display for BlendChunkHeader {
@@ -363,3 +711,20 @@ impl std::fmt::Display for BlendChunkHeader {
         write!(f, "code={} size={} addr=0x{:X?} sdna={} count={}", self.code, self.size, self.addr, self.sdna, self.count)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn reject_short_header() {
+        for header in [&b""[..], b"BLEN", b"BLENDER-v3", b"BLENDER17-01v05"] {
+            match read_header(&mut &header[..]) {
+                Err(UnblendError::BadHeader { field, bytes }) => assert_eq!((field, &bytes[..]), ("length", header)),
+                other => panic!("expected BadHeader for {header:?}, got {other:?}"),
+            }
+        }
+        
+        assert!(read_header(&mut &b"BLENDER-v300"[..]).is_ok());
+    }
+}
@@ -0,0 +1,184 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::blend::*;
+use crate::error::UnblendError;
+use crate::sdna::Sdna;
+use crate::index::*;
+
+/// A blend-file being read block by block.
+///
+/// After [`BlendFile::read_chunk_header`] returned a block, the `Read`-impl of
+/// this type yields the blocks payload; whatever is left unread is skipped
+/// when the next block-header is read.
+pub struct BlendFile<R> {
+    read: R,
+    header: BlendHeader,
+    sdna: Option<Sdna>,
+    index: Option<BlockIndex>,
+    /// Offset of the current block within the (decompressed) file.
+    chunk_offset: u64,
+    chunk: Option<BlendChunkHeader>,
+    /// How much of the current blocks payload is left to be read.
+    remaining: u64,
+    /// The payload of the current block, if it had to be read up-front.
+    buffered: Option<std::io::Cursor<Vec<u8>>>,
+    ended: bool,
+}
+
+impl<R: Read> BlendFile<R> {
+    /// Reads the file-header from the given source.
+    ///
+    /// The DNA only becomes available once its block has been read.
+    pub fn from_reader(mut read: R) -> Result<Self, UnblendError> {
+        let header = read_header(&mut read)?;
+        
+        Ok(Self {
+            read,
+            header,
+            sdna: None,
+            index: None,
+            chunk_offset: header.layout.header_len(),
+            chunk: None,
+            remaining: 0,
+            buffered: None,
+            ended: false,
+        })
+    }
+    
+    pub fn header(&self) -> &BlendHeader {
+        &self.header
+    }
+    
+    /// The location of every block, if the file was opened via [`BlendFile::from_seekable`].
+    pub fn index(&self) -> Option<&BlockIndex> {
+        self.index.as_ref()
+    }
+    
+    /// The parsed `DNA1` block, if it has been read yet.
+    pub fn sdna(&self) -> Option<&Sdna> {
+        self.sdna.as_ref()
+    }
+    
+    /// The header of the block whose payload is currently being read.
+    pub fn chunk(&self) -> Option<&BlendChunkHeader> {
+        self.chunk.as_ref()
+    }
+    
+    /// The offset of the current block-header within the (decompressed) file.
+    pub fn chunk_offset(&self) -> u64 {
+        self.chunk_offset
+    }
+    
+    pub fn into_inner(self) -> R {
+        self.read
+    }
+    
+    /// Skips the rest of the current payload and reads the next block-header.
+    ///
+    /// Returns `None` once the `ENDB` block has been read.
+    pub fn read_chunk_header(&mut self) -> Result<Option<BlendChunkHeader>, UnblendError> {
+        if self.ended {
+            return Ok(None)
+        }
+        
+        std::io::copy(self, &mut std::io::sink())?;
+        self.buffered = None;
+        
+        if let Some(chunk) = self.chunk.take() {
+            self.chunk_offset += self.header.chunk_header_len() + chunk.size;
+        }
+        
+        let chunk = match read_chunk_header(&self.header, &mut self.read) {
+            Ok(chunk) => chunk,
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Err(self.truncated()),
+            Err(err) => return Err(err.into()),
+        };
+        
+        self.chunk = Some(chunk);
+        self.remaining = chunk.size;
+        self.ended = chunk.code == b"ENDB";
+        
+        if chunk.code == b"DNA1" {
+            // Not `read_exact_buffer`, as a crafted size must not allocate up-front.
+            let mut dna1 = Vec::new();
+            self.take(chunk.size).read_to_end(&mut dna1)?;
+            
+            if self.sdna.is_none() {
+                self.sdna = Some(read_dna1(&self.header, &dna1)?);
+            }
+            
+            self.buffered = Some(std::io::Cursor::new(dna1));
+        }
+        
+        Ok(Some(chunk))
+    }
+    
+    fn truncated(&self) -> UnblendError {
+        UnblendError::TruncatedBlock {
+            offset: self.chunk_offset,
+            code: match &self.chunk {
+                Some(chunk) => chunk.code.to_string(),
+                None => String::from("?"),
+            },
+        }
+    }
+}
+
+impl<R: Read + Seek> BlendFile<R> {
+    /// Reads the file-header from the given source, then scans the file
+    /// to build its block-index and parse the DNA, before rewinding to the first block.
+    pub fn from_seekable(read: R) -> Result<Self, UnblendError> {
+        let mut file = Self::from_reader(read)?;
+        let start = file.read.stream_position()?;
+        let index = BlockIndex::scan(&file.header, &mut file.read, start)?;
+        
+        if let Some(entry) = index.find(b"DNA1") {
+            let dna1 = file.read_block(entry)?;
+            file.sdna = Some(read_dna1(&file.header, &dna1)?);
+        }
+        
+        log::info!("Indexed {} block/s.", index.len());
+        
+        file.read.seek(SeekFrom::Start(start))?;
+        file.index = Some(index);
+        Ok(file)
+    }
+    
+    /// Reads the payload of any block, without disturbing the sequential reading.
+    pub fn read_block(&mut self, entry: &BlockIndexEntry) -> Result<Vec<u8>, UnblendError> {
+        let position = self.read.stream_position()?;
+        self.read.seek(SeekFrom::Start(entry.payload.start))?;
+        
+        let mut data = Vec::new();
+        (&mut self.read).take(entry.header.size).read_to_end(&mut data)?;
+        self.read.seek(SeekFrom::Start(position))?;
+        
+        if data.len() as u64 != entry.header.size {
+            return Err(UnblendError::TruncatedBlock { offset: entry.offset, code: entry.header.code.to_string() });
+        }
+        
+        Ok(data)
+    }
+}
+
+impl<R: Read> Read for BlendFile<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if let Some(buffered) = &mut self.buffered {
+            return buffered.read(buf)
+        }
+        
+        if self.remaining == 0 || buf.is_empty() {
+            return Ok(0)
+        }
+        
+        let nom = buf.len().min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+        let got = self.read.read(&mut buf[..nom])?;
+        
+        if got == 0 {
+            return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, self.truncated()))
+        }
+        
+        self.remaining -= got as u64;
+        Ok(got)
+    }
+}
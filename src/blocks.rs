@@ -0,0 +1,69 @@
+use std::io::Read;
+
+use crate::blend::*;
+use crate::blend_file::BlendFile;
+use crate::error::UnblendError;
+use crate::read_ext::{BorrowedTake, ReadExt};
+
+/// Streams the blocks of a blend-file in a single forward pass.
+///
+/// This is not an `Iterator`, as the payload of each [`Block`] borrows the reader,
+/// which the next block has to read from; use it like one via `while let Some(block) = blocks.next_block()`.
+pub struct Blocks<R> {
+    file: BlendFile<R>,
+    done: bool,
+}
+
+/// A block-header together with a reader over its payload.
+pub struct Block<'b, R: Read> {
+    pub header: BlendChunkHeader,
+    /// Offset of the block-header within the (decompressed) file.
+    pub offset: u64,
+    /// The payload; whatever is left unread is skipped by the next call to [`Blocks::next_block`].
+    pub data: BorrowedTake<'b, BlendFile<R>>,
+}
+
+impl<R: Read> Blocks<R> {
+    /// Reads the file-header from the given source.
+    pub fn from_reader(read: R) -> Result<Self, UnblendError> {
+        Ok(Self::new(BlendFile::from_reader(read)?))
+    }
+    
+    /// Continues with the next block of the given file.
+    pub fn new(file: BlendFile<R>) -> Self {
+        Self { file, done: false }
+    }
+    
+    pub fn header(&self) -> &BlendHeader {
+        self.file.header()
+    }
+    
+    pub fn into_inner(self) -> BlendFile<R> {
+        self.file
+    }
+    
+    /// Returns the next block, or `None` after the `ENDB` block or an error.
+    pub fn next_block(&mut self) -> Option<Result<Block<'_, R>, UnblendError>> {
+        if self.done {
+            return None
+        }
+        
+        match self.file.read_chunk_header() {
+            Ok(Some(header)) => {
+                let offset = self.file.chunk_offset();
+                let size = usize::try_from(header.size).unwrap_or(usize::MAX);
+                
+                Some(Ok(Block {
+                    header,
+                    offset,
+                    data: self.file.take_borrowed(size),
+                }))
+            },
+            Ok(None) => None,
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            },
+        }
+    }
+}
@@ -1,7 +1,12 @@
 
-
 pub fn copy<const N: usize>(slice: &[u8]) -> [u8; N] {
     let mut array = [0u8; N];
     array.copy_from_slice(&slice[..N]);
     array
 }
+
+/// Like [`copy`], but returns `None` if the slice is too short.
+pub fn copy_at<const N: usize>(slice: &[u8], from: usize) -> Option<[u8; N]> {
+    let end = from.checked_add(N)?;
+    slice.get(from..end).map(copy::<N>)
+}
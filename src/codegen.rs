@@ -0,0 +1,500 @@
+//! Generation of source code mirroring the structs of an SDNA.
+
+use std::collections::HashSet;
+use std::io::Write;
+
+use crate::blend::*;
+use crate::decode::Primitive;
+use crate::sdna::*;
+
+/// The language to generate code for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodegenLang {
+    Rust,
+    /// A C header.
+    C,
+    /// A Kaitai Struct description of the whole file.
+    Kaitai,
+}
+
+impl std::str::FromStr for CodegenLang {
+    type Err = String;
+    
+    fn from_str(lang: &str) -> Result<Self, Self::Err> {
+        match lang.to_ascii_lowercase().as_str() {
+            "rust" | "rs" => Ok(Self::Rust),
+            "c" | "h" => Ok(Self::C),
+            "kaitai" | "ksy" => Ok(Self::Kaitai),
+            _ => Err(format!("unknown language {lang:?}, expected one of `rust`, `c` or `ksy`")),
+        }
+    }
+}
+
+/// Writes the structs of the SDNA in the given language.
+pub fn write_code(lang: CodegenLang, blend: &BlendHeader, sdna: &Sdna, write: &mut dyn Write) -> std::io::Result<()> {
+    match lang {
+        CodegenLang::Rust => write_rust(blend, sdna, write),
+        CodegenLang::C => write_c(blend, sdna, write),
+        CodegenLang::Kaitai => write_ksy(blend, sdna, write),
+    }
+}
+
+/// Writes a Rust module with one `#[repr(C)]` struct per SDNA struct, in the layout of the file.
+///
+/// - Pointers become `u64` or `u32`, depending on the pointer-size of the file.
+/// - Arrays keep their dimensions, like `[[f32; 2]; 3]` for `float co[3][2]`.
+/// - Bytes of a struct not covered by its fields become a trailing `_pad` array.
+/// - Every struct is followed by a compile-time assertion of its size.
+pub fn write_rust(blend: &BlendHeader, sdna: &Sdna, write: &mut dyn Write) -> std::io::Result<()> {
+    let pointer = match blend.usize {
+        BlendUsize::U32 => "u32",
+        BlendUsize::U64 => "u64",
+    };
+    
+    writeln!(write, "//! The structs of a blend-file of Blender {}, with {}-bit pointers and {:?} byte-order.", blend.version, blend.usize.len() * 8, blend.endian)?;
+    writeln!(write, "//!")?;
+    writeln!(write, "//! Generated by unblend from the `DNA1` block; do not edit.")?;
+    writeln!(write)?;
+    writeln!(write, "#![allow(non_camel_case_types, non_snake_case, dead_code)]")?;
+    
+    let mut defined = HashSet::new();
+    
+    for strct in &sdna.structs {
+        // The first definition wins, should a struct be defined twice.
+        if !defined.insert(strct.type_index) {
+            continue;
+        }
+        
+        let name = rust_ident(&strct.name);
+        let mut used = HashSet::new();
+        
+        writeln!(write)?;
+        writeln!(write, "/// `{}`, SDNA #{}.", strct.name, strct.sdna)?;
+        writeln!(write, "#[repr(C)]")?;
+        writeln!(write, "#[derive(Debug, Clone, Copy)]")?;
+        writeln!(write, "pub struct {name} {{")?;
+        
+        for field in &strct.fields {
+            let element = match field.parsed.is_pointer() {
+                true => String::from(pointer),
+                false => match sdna.struct_by_type(field.type_index) {
+                    Some(inner) => rust_ident(&inner.name),
+                    None => rust_primitive(&field.type_name, sdna.types[field.type_index as usize].size),
+                },
+            };
+            
+            let ty = field.parsed.array_dims.iter().rev().fold(element, |ty, dim| format!("[{ty}; {dim}]"));
+            let ident = unique_ident(&mut used, rust_ident(&field.parsed.ident));
+            
+            writeln!(write, "    /// `{} {}` at 0x{:X?}.", field.type_name, field.name, field.offset)?;
+            writeln!(write, "    pub {ident}: {ty},")?;
+        }
+        
+        if let Some(pad) = (strct.size as usize).checked_sub(strct.fields_size()).filter(|&pad| pad > 0) {
+            let ident = unique_ident(&mut used, String::from("_pad"));
+            writeln!(write, "    pub {ident}: [u8; {pad}],")?;
+        }
+        
+        writeln!(write, "}}")?;
+        writeln!(write)?;
+        writeln!(write, "const _: () = assert!(std::mem::size_of::<{name}>() == {});", strct.size)?;
+    }
+    
+    Ok(())
+}
+
+/// Writes a C header with one struct per SDNA struct, in the layout of the file.
+///
+/// - Pointers become `uint64_t` or `uint32_t`, so that the layout doesn't depend on the machine.
+/// - Structs are ordered so that those embedded by value come first.
+/// - Every struct is followed by a static assertion of its size.
+pub fn write_c(blend: &BlendHeader, sdna: &Sdna, write: &mut dyn Write) -> std::io::Result<()> {
+    let pointer = match blend.usize {
+        BlendUsize::U32 => "uint32_t",
+        BlendUsize::U64 => "uint64_t",
+    };
+    
+    writeln!(write, "/* The structs of a blend-file of Blender {}, with {}-bit pointers and {:?} byte-order.", blend.version, blend.usize.len() * 8, blend.endian)?;
+    writeln!(write, " *")?;
+    writeln!(write, " * Generated by unblend from the `DNA1` block; do not edit. */")?;
+    writeln!(write)?;
+    writeln!(write, "#ifndef UNBLEND_DNA1_H")?;
+    writeln!(write, "#define UNBLEND_DNA1_H")?;
+    writeln!(write)?;
+    writeln!(write, "#include <stdint.h>")?;
+    
+    let structs = by_value_order(sdna);
+    
+    writeln!(write)?;
+    for strct in &structs {
+        let name = c_ident(&strct.name);
+        writeln!(write, "typedef struct {name} {name};")?;
+    }
+    
+    for strct in &structs {
+        let name = c_ident(&strct.name);
+        let mut used = HashSet::new();
+        
+        writeln!(write)?;
+        writeln!(write, "/* SDNA #{}. */", strct.sdna)?;
+        writeln!(write, "struct {name} {{")?;
+        
+        for field in &strct.fields {
+            let mut dims = field.parsed.array_dims.clone();
+            
+            let ty = match field.parsed.is_pointer() {
+                true => String::from(pointer),
+                false => match sdna.struct_by_type(field.type_index) {
+                    Some(inner) => c_ident(&inner.name),
+                    None => match c_primitive(&field.type_name, sdna.types[field.type_index as usize].size) {
+                        Some(ty) => String::from(ty),
+                        None => {
+                            dims.push(sdna.types[field.type_index as usize].size as usize);
+                            String::from("uint8_t")
+                        },
+                    },
+                },
+            };
+            
+            let ident = unique_ident(&mut used, c_ident(&field.parsed.ident));
+            let dims: String = dims.iter().map(|dim| format!("[{dim}]")).collect();
+            
+            writeln!(write, "    {ty} {ident}{dims}; /* {} {} at 0x{:X?} */", field.type_name, field.name, field.offset)?;
+        }
+        
+        if let Some(pad) = (strct.size as usize).checked_sub(strct.fields_size()).filter(|&pad| pad > 0) {
+            let ident = unique_ident(&mut used, String::from("_pad"));
+            writeln!(write, "    uint8_t {ident}[{pad}];")?;
+        }
+        
+        writeln!(write, "}};")?;
+        writeln!(write)?;
+        writeln!(write, "_Static_assert(sizeof({name}) == {}, \"{name}\");", strct.size)?;
+    }
+    
+    writeln!(write)?;
+    writeln!(write, "#endif")?;
+    Ok(())
+}
+
+/// Writes a Kaitai Struct description of the whole file, in its layout.
+///
+/// - The file-header and the block-headers are described as found in the file.
+/// - Blocks whose size fits `count` of their struct are parsed as such, all others as raw bytes.
+/// - Every struct becomes a type named `dna_<name>`; arrays are flattened and `char`-arrays become strings.
+pub fn write_ksy(blend: &BlendHeader, sdna: &Sdna, write: &mut dyn Write) -> std::io::Result<()> {
+    let (pointer, endian) = (format!("u{}", blend.usize.len()), match blend.endian {
+        BlendEndian::LE => "le",
+        BlendEndian::BE => "be",
+    });
+    
+    let mut defined = HashSet::new();
+    let structs: Vec<&SdnaStruct> = sdna.structs.iter().filter(|strct| defined.insert(strct.type_index)).collect();
+    
+    // Every struct gets a unique type name.
+    let mut used = HashSet::new();
+    let type_names: std::collections::HashMap<u16, String> = structs.iter()
+        .map(|strct| (strct.type_index, unique_ident(&mut used, format!("dna_{}", ksy_ident(&strct.name)))))
+        .collect();
+    
+    writeln!(write, "# The layout of a blend-file of Blender {}, with {}-bit pointers and {:?} byte-order.", blend.version, blend.usize.len() * 8, blend.endian)?;
+    writeln!(write, "#")?;
+    writeln!(write, "# Generated by unblend from the `DNA1` block; do not edit.")?;
+    writeln!(write, "meta:")?;
+    writeln!(write, "  id: blend")?;
+    writeln!(write, "  title: Blender {} blend-file", blend.version)?;
+    writeln!(write, "  file-extension: blend")?;
+    writeln!(write, "  endian: {endian}")?;
+    writeln!(write, "seq:")?;
+    writeln!(write, "  - id: header")?;
+    writeln!(write, "    type: file_header")?;
+    writeln!(write, "  - id: blocks")?;
+    writeln!(write, "    type: block")?;
+    writeln!(write, "    repeat: until")?;
+    writeln!(write, "    repeat-until: '_.code == \"ENDB\"'")?;
+    writeln!(write, "instances:")?;
+    writeln!(write, "  struct_sizes:")?;
+    writeln!(write, "    doc: The size of every struct, by SDNA index.")?;
+    let sizes: Vec<String> = sdna.structs.iter().map(|strct| strct.size.to_string()).collect();
+    writeln!(write, "    value: '[{}]'", sizes.join(", "))?;
+    writeln!(write, "types:")?;
+    
+    writeln!(write, "  file_header:")?;
+    writeln!(write, "    seq:")?;
+    writeln!(write, "      - id: magic")?;
+    writeln!(write, "        contents: BLENDER")?;
+    
+    let header_fields: &[(&str, usize)] = match blend.layout {
+        BlendHeaderLayout::Legacy => &[("pointer_size", 1), ("endian", 1), ("version", 3)],
+        BlendHeaderLayout::Large { .. } => &[("header_size", 2), ("dash", 1), ("format_version", 2), ("endian", 1), ("version", 4)],
+    };
+    
+    for (id, size) in header_fields {
+        writeln!(write, "      - id: {id}")?;
+        writeln!(write, "        type: str")?;
+        writeln!(write, "        size: {size}")?;
+        writeln!(write, "        encoding: ASCII")?;
+    }
+    
+    let (size, count) = match blend.layout {
+        BlendHeaderLayout::Legacy => ("u4", "u4"),
+        BlendHeaderLayout::Large { .. } => ("u8", "u8"),
+    };
+    
+    let block_fields: Vec<(&str, &str)> = match blend.layout {
+        BlendHeaderLayout::Legacy => vec![("len_body", size), ("addr", &pointer), ("sdna_index", "u4"), ("count", count)],
+        BlendHeaderLayout::Large { .. } => vec![("sdna_index", "u4"), ("addr", "u8"), ("len_body", size), ("count", count)],
+    };
+    
+    writeln!(write, "  block:")?;
+    writeln!(write, "    seq:")?;
+    writeln!(write, "      - id: code")?;
+    writeln!(write, "        type: str")?;
+    writeln!(write, "        size: 4")?;
+    writeln!(write, "        encoding: ASCII")?;
+    
+    for (id, ty) in block_fields {
+        writeln!(write, "      - id: {id}")?;
+        writeln!(write, "        type: {ty}")?;
+    }
+    
+    writeln!(write, "      - id: body")?;
+    writeln!(write, "        size: len_body")?;
+    writeln!(write, "        if: not is_struct")?;
+    writeln!(write, "      - id: structs")?;
+    writeln!(write, "        type:")?;
+    writeln!(write, "          switch-on: sdna_index")?;
+    writeln!(write, "          cases:")?;
+    for strct in &sdna.structs {
+        writeln!(write, "            {}: {}", strct.sdna, type_names[&strct.type_index])?;
+    }
+    writeln!(write, "        repeat: expr")?;
+    writeln!(write, "        repeat-expr: count")?;
+    writeln!(write, "        if: is_struct")?;
+    writeln!(write, "    instances:")?;
+    writeln!(write, "      is_struct:")?;
+    writeln!(write, "        doc: Whether the body fits `count` of the struct, like for the `DNA1` block it doesn't.")?;
+    writeln!(write, "        value: 'code != \"DNA1\" and code != \"ENDB\" and sdna_index < _root.struct_sizes.size and _root.struct_sizes[sdna_index] != 0 and len_body == _root.struct_sizes[sdna_index] * count'")?;
+    
+    for strct in &structs {
+        let mut used = HashSet::new();
+        
+        writeln!(write, "  {}:", type_names[&strct.type_index])?;
+        writeln!(write, "    doc: '{}, SDNA #{}.'", strct.name, strct.sdna)?;
+        writeln!(write, "    seq:")?;
+        
+        for field in &strct.fields {
+            let ident = unique_ident(&mut used, ksy_ident(&field.parsed.ident));
+            let dims = &field.parsed.array_dims;
+            
+            writeln!(write, "      - id: {ident}")?;
+            writeln!(write, "        doc: '{} {} at 0x{:X?}'", field.type_name, field.name.replace('\'', "''"), field.offset)?;
+            
+            let string = !field.parsed.is_pointer() && field.type_name == "char" && !dims.is_empty();
+            let len: usize = match string {
+                true => dims[..dims.len() - 1].iter().product(),
+                false => field.parsed.array_len(),
+            };
+            
+            if field.parsed.is_pointer() {
+                writeln!(write, "        type: {pointer}")?;
+            } else if string {
+                writeln!(write, "        type: strz")?;
+                writeln!(write, "        size: {}", dims[dims.len() - 1])?;
+                writeln!(write, "        encoding: UTF-8")?;
+            } else if let Some(inner) = sdna.struct_by_type(field.type_index) {
+                writeln!(write, "        type: {}", type_names[&inner.type_index])?;
+            } else {
+                let size = sdna.types[field.type_index as usize].size;
+                match ksy_primitive(&field.type_name, size) {
+                    Some(ty) => writeln!(write, "        type: {ty}")?,
+                    None => writeln!(write, "        size: {size}")?,
+                }
+            }
+            
+            let repeat = match string {
+                true => dims.len() > 1,
+                false => !dims.is_empty(),
+            };
+            
+            if repeat {
+                writeln!(write, "        repeat: expr")?;
+                writeln!(write, "        repeat-expr: {len}")?;
+            }
+        }
+        
+        if let Some(pad) = (strct.size as usize).checked_sub(strct.fields_size()).filter(|&pad| pad > 0) {
+            writeln!(write, "      - id: {}", unique_ident(&mut used, String::from("pad")))?;
+            writeln!(write, "        size: {pad}")?;
+        }
+    }
+    
+    Ok(())
+}
+
+/// The structs of the SDNA, ordered so that every struct comes after those it embeds by value.
+fn by_value_order(sdna: &Sdna) -> Vec<&SdnaStruct> {
+    fn visit<'s>(sdna: &'s Sdna, strct: &'s SdnaStruct, visited: &mut HashSet<u16>, order: &mut Vec<&'s SdnaStruct>) {
+        if !visited.insert(strct.type_index) {
+            return
+        }
+        
+        for field in strct.fields.iter().filter(|field| !field.parsed.is_pointer()) {
+            if let Some(inner) = sdna.struct_by_type(field.type_index) {
+                visit(sdna, inner, visited, order);
+            }
+        }
+        
+        order.push(strct);
+    }
+    
+    let mut visited = HashSet::new();
+    let mut order = Vec::with_capacity(sdna.structs.len());
+    
+    for strct in &sdna.structs {
+        // Via the lookup, so that the first definition wins, should a struct be defined twice.
+        if let Some(strct) = sdna.struct_by_type(strct.type_index) {
+            visit(sdna, strct, &mut visited, &mut order);
+        }
+    }
+    
+    order
+}
+
+/// The Rust type of a primitive, by its name and size; unknown types become byte-arrays.
+fn rust_primitive(type_name: &str, size: u16) -> String {
+    let ty = match (Primitive::from_type_name(type_name), size) {
+        (Some(Primitive::Int), 1) => "i8",
+        (Some(Primitive::Int), 2) => "i16",
+        (Some(Primitive::Int), 4) => "i32",
+        (Some(Primitive::Int), 8) => "i64",
+        (Some(Primitive::UInt), 1) => "u8",
+        (Some(Primitive::UInt), 2) => "u16",
+        (Some(Primitive::UInt), 4) => "u32",
+        (Some(Primitive::UInt), 8) => "u64",
+        (Some(Primitive::Float), 4) => "f32",
+        (Some(Primitive::Float), 8) => "f64",
+        _ => return format!("[u8; {size}]"),
+    };
+    
+    String::from(ty)
+}
+
+/// The C type of a primitive, by its name and size; `None` for unknown types.
+fn c_primitive(type_name: &str, size: u16) -> Option<&'static str> {
+    let ty = match (Primitive::from_type_name(type_name), size) {
+        _ if type_name == "char" => "char",
+        (Some(Primitive::Int), 1) => "int8_t",
+        (Some(Primitive::Int), 2) => "int16_t",
+        (Some(Primitive::Int), 4) => "int32_t",
+        (Some(Primitive::Int), 8) => "int64_t",
+        (Some(Primitive::UInt), 1) => "uint8_t",
+        (Some(Primitive::UInt), 2) => "uint16_t",
+        (Some(Primitive::UInt), 4) => "uint32_t",
+        (Some(Primitive::UInt), 8) => "uint64_t",
+        (Some(Primitive::Float), 4) => "float",
+        (Some(Primitive::Float), 8) => "double",
+        _ => return None,
+    };
+    
+    Some(ty)
+}
+
+/// The Kaitai type of a primitive, by its name and size; `None` for unknown types.
+fn ksy_primitive(type_name: &str, size: u16) -> Option<&'static str> {
+    let ty = match (Primitive::from_type_name(type_name), size) {
+        (Some(Primitive::Int), 1) => "s1",
+        (Some(Primitive::Int), 2) => "s2",
+        (Some(Primitive::Int), 4) => "s4",
+        (Some(Primitive::Int), 8) => "s8",
+        (Some(Primitive::UInt), 1) => "u1",
+        (Some(Primitive::UInt), 2) => "u2",
+        (Some(Primitive::UInt), 4) => "u4",
+        (Some(Primitive::UInt), 8) => "u8",
+        (Some(Primitive::Float), 4) => "f4",
+        (Some(Primitive::Float), 8) => "f8",
+        _ => return None,
+    };
+    
+    Some(ty)
+}
+
+/// Replaces everything but ASCII letters, digits and underscores, so that the name doesn't start with a digit.
+fn plain_ident(name: &str) -> String {
+    let mut ident: String = name.chars()
+        .map(|char| match char.is_ascii_alphanumeric() {
+            true => char,
+            false => '_',
+        })
+        .collect();
+    
+    if ident.is_empty() || ident.starts_with(|char: char| char.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    
+    ident
+}
+
+/// Makes a name a valid C identifier, escaping keywords.
+fn c_ident(name: &str) -> String {
+    let ident = plain_ident(name);
+    
+    match ident.as_str() {
+        "auto" | "break" | "case" | "char" | "const" | "continue" | "default" | "do" | "double" | "else" | "enum"
+        | "extern" | "float" | "for" | "goto" | "if" | "inline" | "int" | "long" | "register" | "restrict"
+        | "return" | "short" | "signed" | "sizeof" | "static" | "struct" | "switch" | "typedef" | "union"
+        | "unsigned" | "void" | "volatile" | "while" | "bool" | "true" | "false" => format!("{ident}_"),
+        _ => ident,
+    }
+}
+
+/// Makes a name a valid Kaitai identifier: lower snake-case, starting with a letter.
+fn ksy_ident(name: &str) -> String {
+    let mut ident = String::with_capacity(name.len());
+    let mut previous = '_';
+    
+    for char in plain_ident(name).chars() {
+        if char.is_ascii_uppercase() && (previous.is_ascii_lowercase() || previous.is_ascii_digit()) {
+            ident.push('_');
+        }
+        
+        ident.push(char.to_ascii_lowercase());
+        previous = char;
+    }
+    
+    let ident = ident.trim_matches('_').replace("__", "_");
+    
+    match ident.starts_with(|char: char| char.is_ascii_lowercase()) {
+        true => ident,
+        false => format!("f_{ident}"),
+    }
+}
+
+/// Makes a name a valid Rust identifier, escaping keywords.
+fn rust_ident(name: &str) -> String {
+    let ident = plain_ident(name);
+    
+    match ident.as_str() {
+        // These can't be raw identifiers.
+        "_" | "self" | "Self" | "super" | "crate" => format!("{ident}_"),
+        "as" | "async" | "await" | "box" | "break" | "const" | "continue" | "dyn" | "else" | "enum" | "extern"
+        | "false" | "fn" | "for" | "gen" | "if" | "impl" | "in" | "let" | "loop" | "match" | "mod" | "move" | "mut"
+        | "pub" | "ref" | "return" | "static" | "struct" | "trait" | "true" | "try" | "type" | "unsafe" | "use"
+        | "where" | "while" | "yield" | "abstract" | "become" | "do" | "final" | "macro" | "override" | "priv"
+        | "typeof" | "unsized" | "virtual" => format!("r#{ident}"),
+        _ => ident,
+    }
+}
+
+/// Appends `_1`, `_2`, etc. to an identifier until it is unique within a struct.
+fn unique_ident(used: &mut HashSet<String>, ident: String) -> String {
+    let mut candidate = ident.clone();
+    let mut suffix = 0;
+    
+    while !used.insert(candidate.clone()) {
+        suffix += 1;
+        candidate = format!("{ident}_{suffix}");
+    }
+    
+    candidate
+}
@@ -0,0 +1,370 @@
+//! Conversion of blend-files to another pointer-size and endianness, thru their SDNA.
+
+use std::io::{Read, Seek, Write};
+
+use crate::blend::*;
+use crate::blend_file::BlendFile;
+use crate::decode::*;
+use crate::error::UnblendError;
+use crate::pointers::*;
+use crate::sdna::*;
+
+/// The pointer-size and endianness to convert to, like `64le`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConvertTarget {
+    pub usize: BlendUsize,
+    pub endian: BlendEndian,
+}
+
+impl std::str::FromStr for ConvertTarget {
+    type Err = String;
+    
+    fn from_str(target: &str) -> Result<Self, Self::Err> {
+        let (usize, endian) = match target.to_ascii_lowercase().as_str() {
+            "32le" => (BlendUsize::U32, BlendEndian::LE),
+            "32be" => (BlendUsize::U32, BlendEndian::BE),
+            "64le" => (BlendUsize::U64, BlendEndian::LE),
+            "64be" => (BlendUsize::U64, BlendEndian::BE),
+            _ => return Err(format!("unknown target {target:?}, expected one of 32le, 32be, 64le or 64be")),
+        };
+        
+        Ok(Self { usize, endian })
+    }
+}
+
+/// How much of a file could be converted.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConvertStats {
+    pub blocks: usize,
+    /// Blocks converted thru their struct, or as array of pointers.
+    pub converted: usize,
+    /// Blocks without struct, whose payload was copied as-is.
+    pub raw: usize,
+}
+
+/// How the payload of a block is laid out.
+#[derive(Debug, Clone, Copy)]
+enum Layout {
+    /// `count` structs, by SDNA index.
+    Struct(usize),
+    PointerArray,
+    Raw,
+    Dna1,
+}
+
+/// Converts an indexed file to the given target, writing a new file.
+///
+/// - Structs are rewritten field by field: primitives are byte-swapped and pointers widened or narrowed.
+/// - The sizes of all structs are recomputed for the new pointer-size, and the `DNA1` block is rewritten.
+/// - If the pointer-size changes, blocks are given new, consecutive addresses.
+/// - Blocks without struct can't be converted and are copied as-is.
+///   As their bytes would stay in the old order, changing the endianness fails if there are any, unless `keep_raw` is set.
+pub fn convert<R: Read + Seek>(blend_file: &mut BlendFile<R>, target: ConvertTarget, keep_raw: bool, write: &mut dyn Write) -> Result<ConvertStats, UnblendError> {
+    let blend = *blend_file.header();
+    let index = blend_file.index().cloned().unwrap_or_default();
+    let old_sdna = blend_file.sdna().cloned()
+        .ok_or_else(|| UnblendError::malformed_dna(0, "the file has no DNA1 block"))?;
+    let new_sdna = retarget_sdna(&old_sdna, target.usize.len() as usize)?;
+    
+    let new_blend = BlendHeader {
+        // The large header always comes with 64-bit pointers.
+        layout: match target.usize {
+            BlendUsize::U32 => BlendHeaderLayout::Legacy,
+            BlendUsize::U64 => blend.layout,
+        },
+        usize: target.usize,
+        endian: target.endian,
+        version: blend.version,
+    };
+    
+    let decoder = Decoder::new(&blend, &old_sdna);
+    let address_map = AddressMap::new(&index);
+    
+    // The layout and new size of every block.
+    let mut layouts = Vec::with_capacity(index.len());
+    let mut sizes = Vec::with_capacity(index.len());
+    
+    for entry in index.iter() {
+        let header = &entry.header;
+        
+        let layout = match decoder.block_struct(header) {
+            _ if header.code == b"DNA1" => Layout::Dna1,
+            Some(strct) => Layout::Struct(strct.sdna),
+            None if address_map.is_pointer_array(&decoder, &blend_file.read_block(entry)?) => Layout::PointerArray,
+            None => Layout::Raw,
+        };
+        
+        sizes.push(match layout {
+            Layout::Struct(sdna) => new_sdna.structs[sdna].size as u64 * header.count,
+            Layout::PointerArray => header.size / blend.usize.len() as u64 * target.usize.len() as u64,
+            Layout::Raw => header.size,
+            Layout::Dna1 => encode_dna1(target.endian, &new_sdna).len() as u64,
+        });
+        
+        layouts.push(layout);
+    }
+    
+    let raw = index.iter().zip(&layouts)
+        .filter(|(entry, layout)| matches!(layout, Layout::Raw) && header_has_payload(&entry.header))
+        .count();
+    
+    if raw > 0 && blend.endian != target.endian && !keep_raw {
+        return Err(UnblendError::UnswappableBlocks(raw));
+    }
+    
+    // Blocks keep their address, unless they have to move as their sizes change.
+    let addrs: Vec<u64> = match blend.usize == target.usize {
+        true => index.iter().map(|entry| entry.header.addr).collect(),
+        false => {
+            let mut next = 0x1000u64;
+            index.iter().zip(&sizes).map(|(entry, size)| {
+                if entry.header.addr == 0 {
+                    return 0
+                }
+                
+                let addr = next;
+                next = (next + size.max(&1)).next_multiple_of(16);
+                addr
+            }).collect()
+        },
+    };
+    
+    let converter = Converter {
+        from: blend,
+        to: new_blend,
+        old_sdna: &old_sdna,
+        new_sdna: &new_sdna,
+        address_map: &address_map,
+        layouts: &layouts,
+        addrs: &addrs,
+    };
+    
+    write_header(&new_blend, write)?;
+    let mut stats = ConvertStats::default();
+    
+    for (block, entry) in index.iter().enumerate() {
+        let data = blend_file.read_block(entry)?;
+        
+        let payload = match layouts[block] {
+            Layout::Struct(sdna) => {
+                let (old, new) = (&old_sdna.structs[sdna], &new_sdna.structs[sdna]);
+                let mut payload = vec![0u8; sizes[block] as usize];
+                
+                for (element, data) in data.chunks_exact(old.size as usize).enumerate() {
+                    let out = &mut payload[element * new.size as usize..(element + 1) * new.size as usize];
+                    converter.convert_struct(old, new, data, out, 0);
+                }
+                
+                stats.converted += 1;
+                payload
+            },
+            Layout::PointerArray => {
+                let mut payload = vec![0u8; sizes[block] as usize];
+                
+                for (element, data) in data.chunks_exact(blend.usize.len() as usize).enumerate() {
+                    let size = target.usize.len() as usize;
+                    converter.convert_pointer(data, &mut payload[element * size..(element + 1) * size]);
+                }
+                
+                stats.converted += 1;
+                payload
+            },
+            Layout::Raw => {
+                if header_has_payload(&entry.header) {
+                    stats.raw += 1;
+                }
+                data
+            },
+            Layout::Dna1 => encode_dna1(target.endian, &new_sdna),
+        };
+        
+        let chunk = BlendChunkHeader {
+            size: payload.len() as u64,
+            addr: addrs[block],
+            ..entry.header
+        };
+        
+        write_chunk_header(&new_blend, &chunk, write)?;
+        write.write_all(&payload)?;
+        stats.blocks += 1;
+    }
+    
+    write.flush()?;
+    Ok(stats)
+}
+
+fn header_has_payload(chunk: &BlendChunkHeader) -> bool {
+    chunk.size > 0 && chunk.code != b"ENDB"
+}
+
+/// Recomputes the size of every struct for another pointer-size.
+///
+/// Bytes of a struct not covered by its fields are kept.
+/// Fails if a field gets too large to be laid out.
+pub fn retarget_sdna(sdna: &Sdna, pointer_size: usize) -> Result<Sdna, UnblendError> {
+    fn size_of(sdna: &Sdna, pointer_size: usize, type_index: u16, sizes: &mut Vec<Option<u16>>, depth: usize) -> u16 {
+        if let Some(size) = sizes[type_index as usize] {
+            return size
+        }
+        
+        let old_size = sdna.types[type_index as usize].size;
+        
+        let Some(strct) = sdna.struct_by_type(type_index).filter(|_| depth <= MAX_DEPTH) else {
+            return old_size
+        };
+        
+        // A struct embedding itself keeps its size, instead of being visited again for every field.
+        sizes[type_index as usize] = Some(old_size);
+        
+        let fields_size = strct.fields.iter()
+            .map(|field| match field.parsed.is_pointer() {
+                true => pointer_size.saturating_mul(field.parsed.array_len()),
+                false => (size_of(sdna, pointer_size, field.type_index, sizes, depth + 1) as usize).saturating_mul(field.parsed.array_len()),
+            })
+            .fold(0usize, usize::saturating_add);
+        
+        let size = (old_size as usize).saturating_add(fields_size).saturating_sub(strct.fields_size()).min(u16::MAX as usize) as u16;
+        sizes[type_index as usize] = Some(size);
+        size
+    }
+    
+    let mut sizes = vec![None; sdna.types.len()];
+    let lengths = (0..sdna.types.len())
+        .map(|type_index| size_of(sdna, pointer_size, type_index as u16, &mut sizes, 0))
+        .collect();
+    
+    Sdna::new(
+        sdna.names.clone(),
+        sdna.types.iter().map(|stype| stype.name.clone()).collect(),
+        lengths,
+        sdna.structs.iter()
+            .map(|strct| (strct.type_index, strct.fields.iter().map(|field| (field.type_index, field.name_index)).collect()))
+            .collect(),
+        pointer_size,
+    )
+}
+
+struct Converter<'c> {
+    from: BlendHeader,
+    to: BlendHeader,
+    old_sdna: &'c Sdna,
+    new_sdna: &'c Sdna,
+    address_map: &'c AddressMap,
+    layouts: &'c [Layout],
+    /// The new address of every block, by index.
+    addrs: &'c [u64],
+}
+
+impl Converter<'_> {
+    fn convert_struct(&self, old: &SdnaStruct, new: &SdnaStruct, data: &[u8], out: &mut [u8], depth: usize) {
+        // Bytes not covered by any field are carried over verbatim.
+        let (old_end, new_end) = (old.fields_size(), new.fields_size());
+        if let (Some(rest), Some(out_rest)) = (data.get(old_end..), out.get_mut(new_end..)) {
+            let len = rest.len().min(out_rest.len());
+            out_rest[..len].copy_from_slice(&rest[..len]);
+        }
+        
+        if !is_decodable(old, data.len(), depth) || !is_decodable(new, out.len(), depth) {
+            return
+        }
+        
+        for (old_field, new_field) in old.fields.iter().zip(&new.fields) {
+            let len = old_field.parsed.array_len();
+            if len == 0 || !is_expandable(old_field) {
+                continue;
+            }
+            
+            let (old_size, new_size) = (old_field.size / len, new_field.size / len);
+            let inner = self.old_sdna.struct_by_type(old_field.type_index)
+                .zip(self.new_sdna.struct_by_type(new_field.type_index))
+                .filter(|_| !old_field.parsed.is_pointer());
+            
+            for index in 0..len {
+                let data = &data[old_field.offset + index * old_size..][..old_size];
+                let out = &mut out[new_field.offset + index * new_size..][..new_size];
+                
+                match inner {
+                    _ if old_field.parsed.is_pointer() => self.convert_pointer(data, out),
+                    Some((old, new)) => self.convert_struct(old, new, data, out, depth + 1),
+                    None => {
+                        out.copy_from_slice(data);
+                        if self.from.endian != self.to.endian {
+                            out.reverse();
+                        }
+                    },
+                }
+            }
+        }
+    }
+    
+    fn convert_pointer(&self, data: &[u8], out: &mut [u8]) {
+        let addr = match self.from.usize {
+            BlendUsize::U32 => crate::byte_ext::copy_at::<4>(data, 0).map(|bytes| self.from.endian.u32(bytes) as u64),
+            BlendUsize::U64 => crate::byte_ext::copy_at::<8>(data, 0).map(|bytes| self.from.endian.u64(bytes)),
+        }.unwrap_or(0);
+        
+        let addr = self.map(addr);
+        
+        match self.to.usize {
+            BlendUsize::U32 => out[..4].copy_from_slice(&self.to.endian.u32_bytes(addr as u32)),
+            BlendUsize::U64 => out[..8].copy_from_slice(&self.to.endian.u64_bytes(addr)),
+        }
+    }
+    
+    /// Maps an old address to the new one, following the block as it moved and its fields as they shifted.
+    ///
+    /// Dangling pointers are kept, unless they don't fit the new pointer-size.
+    fn map(&self, addr: u64) -> u64 {
+        let target = match self.address_map.resolve(addr) {
+            Resolution::Null => return 0,
+            Resolution::Block(target) => target,
+            Resolution::Dangling => return match self.to.usize {
+                BlendUsize::U32 if addr > u32::MAX as u64 => 0,
+                _ => addr,
+            },
+        };
+        
+        let offset = target.offset as usize;
+        let old_pointer = self.from.usize.len() as usize;
+        let new_pointer = self.to.usize.len() as usize;
+        
+        let offset = match self.layouts[target.block] {
+            Layout::Struct(sdna) => {
+                let (old, new) = (&self.old_sdna.structs[sdna], &self.new_sdna.structs[sdna]);
+                let element = offset / old.size as usize;
+                element * new.size as usize + self.map_offset(old, new, offset % old.size as usize, 0)
+            },
+            Layout::PointerArray => offset / old_pointer * new_pointer + offset % old_pointer,
+            Layout::Raw | Layout::Dna1 => offset,
+        };
+        
+        self.addrs[target.block] + offset as u64
+    }
+    
+    /// Maps an offset within an old struct to the same place within the new one.
+    fn map_offset(&self, old: &SdnaStruct, new: &SdnaStruct, offset: usize, depth: usize) -> usize {
+        let field = old.fields.iter()
+            .zip(&new.fields)
+            .find(|(old_field, _)| old_field.offset <= offset && offset < old_field.offset + old_field.size);
+        
+        let Some((old_field, new_field)) = field.filter(|_| depth <= MAX_DEPTH) else {
+            // Past the last field, or nested too deep.
+            return (offset + new.fields_size()).saturating_sub(old.fields_size())
+        };
+        
+        let len = old_field.parsed.array_len().max(1);
+        let (old_size, new_size) = (old_field.size / len, new_field.size / len);
+        let within = offset - old_field.offset;
+        let base = new_field.offset + within / old_size * new_size;
+        let rest = within % old_size;
+        
+        let inner = self.old_sdna.struct_by_type(old_field.type_index)
+            .zip(self.new_sdna.struct_by_type(new_field.type_index))
+            .filter(|_| !old_field.parsed.is_pointer());
+        
+        match inner {
+            Some((old, new)) => base + self.map_offset(old, new, rest, depth + 1),
+            None => base + rest.min(new_size.saturating_sub(1)),
+        }
+    }
+}
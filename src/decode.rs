@@ -0,0 +1,409 @@
+//! Decoding of block payloads into [`Value`]-trees, as described by the SDNA.
+
+use crate::blend::*;
+use crate::byte_ext::copy_at;
+use crate::sdna::*;
+
+/// Deeper nesting than this is assumed to be a self-referencing struct.
+pub const MAX_DEPTH: usize = 64;
+
+/// A decoded piece of a block payload.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Struct {
+        name: String,
+        fields: Vec<(String, Value)>,
+    },
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    /// A `char`-array, up to the first nul.
+    String(String),
+    Array(Vec<Value>),
+    /// An old memory address.
+    Pointer(u64),
+    /// Bytes of a type the decoder knows nothing about.
+    Raw(Vec<u8>),
+}
+
+/// The kind of a primitive type, which is only known by its name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Primitive {
+    Int,
+    UInt,
+    Float,
+}
+
+impl Primitive {
+    pub fn from_type_name(name: &str) -> Option<Self> {
+        match name {
+            "char" | "short" | "int" | "long" | "int8_t" | "int16_t" | "int32_t" | "int64_t" => Some(Primitive::Int),
+            "uchar" | "ushort" | "uint" | "ulong" | "uint8_t" | "uint16_t" | "uint32_t" | "uint64_t" => Some(Primitive::UInt),
+            "float" | "double" => Some(Primitive::Float),
+            _ => None,
+        }
+    }
+}
+
+/// Interprets block payloads thru the SDNA of their file.
+pub struct Decoder<'s> {
+    sdna: &'s Sdna,
+    endian: BlendEndian,
+    pointer_size: usize,
+}
+
+impl<'s> Decoder<'s> {
+    pub fn new(blend: &BlendHeader, sdna: &'s Sdna) -> Self {
+        Self {
+            sdna,
+            endian: blend.endian,
+            pointer_size: blend.usize.len() as usize,
+        }
+    }
+    
+    pub fn sdna(&self) -> &'s Sdna {
+        self.sdna
+    }
+    
+    /// The struct a block consists of, if its payload actually fits `count` of them.
+    /// 
+    /// Blender writes raw data with SDNA index 0, so such blocks are only decoded as struct #0
+    /// if their code says they are a datablock; blocks without elements, like `ENDB`, never are.
+    pub fn block_struct(&self, chunk: &BlendChunkHeader) -> Option<&'s SdnaStruct> {
+        if chunk.count == 0 || (chunk.sdna == 0 && !chunk.code.is_id()) {
+            return None
+        }
+        
+        let strct = self.sdna.struct_by_sdna(chunk.sdna)?;
+        
+        if strct.size == 0 || Some(chunk.size) != (strct.size as u64).checked_mul(chunk.count) {
+            return None
+        }
+        
+        Some(strct)
+    }
+    
+    /// Decodes a whole block payload: a single struct, or an array if `count` is more than one.
+    ///
+    /// Payloads that don't fit their struct are returned as [`Value::Raw`].
+    pub fn decode_block(&self, chunk: &BlendChunkHeader, data: &[u8]) -> Value {
+        let Some(strct) = self.block_struct(chunk).filter(|_| data.len() as u64 == chunk.size) else {
+            return Value::Raw(data.to_vec())
+        };
+        
+        if chunk.count == 1 {
+            return self.decode_struct(strct, data)
+        }
+        
+        Value::Array(data.chunks_exact(strct.size as usize)
+            .map(|element| self.decode_struct(strct, element))
+            .collect())
+    }
+    
+    pub fn decode_struct(&self, strct: &SdnaStruct, data: &[u8]) -> Value {
+        self.decode_struct_at(strct, data, 0)
+    }
+    
+    /// The path and offset of every pointer within a struct, including those of nested structs.
+    /// 
+    /// The paths are the same as those of [`Value::pointers`].
+    pub fn pointer_fields(&self, strct: &SdnaStruct) -> Vec<(String, usize)> {
+        let mut pointers = Vec::new();
+        self.collect_pointer_fields(strct, "", 0, 0, &mut pointers);
+        pointers
+    }
+    
+    fn collect_pointer_fields(&self, strct: &SdnaStruct, prefix: &str, base: usize, depth: usize, pointers: &mut Vec<(String, usize)>) {
+        // Such structs are decoded as raw bytes.
+        if !is_decodable(strct, strct.size as usize, depth) {
+            return
+        }
+        
+        for field in strct.fields.iter().filter(|field| is_expandable(field)) {
+            let inner = match field.parsed.is_pointer() {
+                true => None,
+                false => match self.sdna.struct_by_type(field.type_index) {
+                    Some(inner) => Some(inner),
+                    None => continue,
+                },
+            };
+            
+            let len = field.parsed.array_len();
+            let element_size = field.size.checked_div(len).unwrap_or(0);
+            
+            for index in 0..len {
+                let mut path = match prefix {
+                    "" => field.parsed.ident.clone(),
+                    prefix => format!("{prefix}.{}", field.parsed.ident),
+                };
+                
+                // Splits the flat index along the dimensions, outermost first.
+                let mut rest = index;
+                let mut indices = Vec::with_capacity(field.parsed.array_dims.len());
+                for dim in field.parsed.array_dims.iter().rev() {
+                    indices.push(rest % dim);
+                    rest /= dim;
+                }
+                for index in indices.iter().rev() {
+                    path.push_str(&format!("[{index}]"));
+                }
+                
+                let offset = base + field.offset + index * element_size;
+                
+                match inner {
+                    Some(inner) => self.collect_pointer_fields(inner, &path, offset, depth + 1, pointers),
+                    None => pointers.push((path, offset)),
+                }
+            }
+        }
+    }
+    
+    pub fn pointer_size(&self) -> usize {
+        self.pointer_size
+    }
+    
+    /// Reads a pointer-sized value.
+    pub fn read_pointer(&self, data: &[u8], offset: usize) -> Option<u64> {
+        match self.pointer_size {
+            4 => copy_at::<4>(data, offset).map(|bytes| self.endian.u32(bytes).into()),
+            _ => copy_at::<8>(data, offset).map(|bytes| self.endian.u64(bytes)),
+        }
+    }
+    
+    fn decode_struct_at(&self, strct: &SdnaStruct, data: &[u8], depth: usize) -> Value {
+        if !is_decodable(strct, data.len(), depth) {
+            return Value::Raw(data.to_vec())
+        }
+        
+        let fields = strct.fields.iter()
+            .map(|field| {
+                let data = &data[field.offset..field.offset + field.size];
+                (field.parsed.ident.clone(), self.decode_field(field, data, depth))
+            })
+            .collect();
+        
+        Value::Struct {
+            name: strct.name.clone(),
+            fields,
+        }
+    }
+    
+    fn decode_field(&self, field: &SdnaField, data: &[u8], depth: usize) -> Value {
+        let dims = &field.parsed.array_dims;
+        
+        if !is_expandable(field) {
+            return Value::Raw(Vec::new())
+        }
+        
+        // A `char`-array is a string, or an array of them for more dimensions.
+        if !field.parsed.is_pointer() && field.type_name == "char" && !dims.is_empty() {
+            let len = dims[dims.len() - 1];
+            return self.decode_array(&dims[..dims.len() - 1], data, len, &|data| decode_string(data))
+        }
+        
+        let element_size = match dims.iter().product::<usize>() {
+            0 => return Value::Array(Vec::new()),
+            len => data.len() / len,
+        };
+        
+        self.decode_array(dims, data, element_size, &|data| {
+            if field.parsed.is_pointer() {
+                return self.decode_pointer(data)
+            }
+            
+            match self.sdna.struct_by_type(field.type_index) {
+                Some(strct) => self.decode_struct_at(strct, data, depth + 1),
+                None => self.decode_primitive(&field.type_name, data),
+            }
+        })
+    }
+    
+    /// Splits `data` along the given dimensions, decoding the innermost elements with `element`.
+    fn decode_array(&self, dims: &[usize], data: &[u8], element_size: usize, element: &dyn Fn(&[u8]) -> Value) -> Value {
+        let Some((&len, inner)) = dims.split_first() else {
+            return element(data)
+        };
+        
+        let stride = inner.iter().product::<usize>() * element_size;
+        
+        Value::Array((0..len)
+            .map(|index| self.decode_array(inner, &data[index * stride..(index + 1) * stride], element_size, element))
+            .collect())
+    }
+    
+    fn decode_pointer(&self, data: &[u8]) -> Value {
+        self.read_pointer(data, 0)
+            .map(Value::Pointer)
+            .unwrap_or_else(|| Value::Raw(data.to_vec()))
+    }
+    
+    fn decode_primitive(&self, type_name: &str, data: &[u8]) -> Value {
+        let endian = self.endian;
+        
+        let value = match (Primitive::from_type_name(type_name), data.len()) {
+            (Some(Primitive::Int), 1) => Some(Value::Int(data[0] as i8 as i64)),
+            (Some(Primitive::Int), 2) => copy_at::<2>(data, 0).map(|b| Value::Int(endian.u16(b) as i16 as i64)),
+            (Some(Primitive::Int), 4) => copy_at::<4>(data, 0).map(|b| Value::Int(endian.u32(b) as i32 as i64)),
+            (Some(Primitive::Int), 8) => copy_at::<8>(data, 0).map(|b| Value::Int(endian.u64(b) as i64)),
+            (Some(Primitive::UInt), 1) => Some(Value::UInt(data[0] as u64)),
+            (Some(Primitive::UInt), 2) => copy_at::<2>(data, 0).map(|b| Value::UInt(endian.u16(b) as u64)),
+            (Some(Primitive::UInt), 4) => copy_at::<4>(data, 0).map(|b| Value::UInt(endian.u32(b) as u64)),
+            (Some(Primitive::UInt), 8) => copy_at::<8>(data, 0).map(|b| Value::UInt(endian.u64(b))),
+            (Some(Primitive::Float), 4) => copy_at::<4>(data, 0).map(|b| Value::Float(f32::from_bits(endian.u32(b)) as f64)),
+            (Some(Primitive::Float), 8) => copy_at::<8>(data, 0).map(|b| Value::Float(f64::from_bits(endian.u64(b)))),
+            _ => None,
+        };
+        
+        value.unwrap_or_else(|| Value::Raw(data.to_vec()))
+    }
+}
+
+/// Whether a struct can be decoded from `len` bytes, at the given depth of nesting.
+pub fn is_decodable(strct: &SdnaStruct, len: usize, depth: usize) -> bool {
+    depth <= MAX_DEPTH && strct.fields_size() <= len
+}
+
+/// Whether the elements of a field are worth descending into.
+///
+/// Fields without any bytes are not: a zero-size struct may embed itself as an array,
+/// which would branch out exponentially before reaching [`MAX_DEPTH`].
+/// With every element taking up at least one byte, each level of nesting
+/// yields at most as many values as the payload has bytes.
+pub fn is_expandable(field: &SdnaField) -> bool {
+    field.size > 0
+}
+
+fn decode_string(data: &[u8]) -> Value {
+    let len = data.iter().position(|&byte| byte == 0).unwrap_or(data.len());
+    Value::String(String::from_utf8_lossy(&data[..len]).into_owned())
+}
+
+impl Value {
+    /// The value of a struct field, by its bare identifier.
+    pub fn field(&self, ident: &str) -> Option<&Value> {
+        match self {
+            Value::Struct { fields, .. } => fields.iter().find(|(name, _)| name == ident).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+    
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(string) => Some(string),
+            _ => None,
+        }
+    }
+    
+    /// Any integer, as long as it fits.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Int(int) => Some(*int),
+            Value::UInt(uint) => i64::try_from(*uint).ok(),
+            _ => None,
+        }
+    }
+    
+    /// The name of a datablock, i.e. its `id.name` without the two-letter code it starts with.
+    pub fn id_name(&self) -> Option<&str> {
+        let name = self.field("id")?.field("name")?.as_str()?;
+        Some(name.get(2..).unwrap_or(name))
+    }
+    
+    /// Every pointer within this value, by the path of its field, like `id.next` or `mat[1]`.
+    pub fn pointers(&self) -> Vec<(String, u64)> {
+        fn collect(value: &Value, path: &mut String, pointers: &mut Vec<(String, u64)>) {
+            let len = path.len();
+            
+            match value {
+                Value::Pointer(addr) => pointers.push((path.clone(), *addr)),
+                Value::Struct { fields, .. } => for (name, value) in fields {
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(name);
+                    collect(value, path, pointers);
+                    path.truncate(len);
+                },
+                Value::Array(values) => for (index, value) in values.iter().enumerate() {
+                    path.push_str(&format!("[{index}]"));
+                    collect(value, path, pointers);
+                    path.truncate(len);
+                },
+                _ => (),
+            }
+        }
+        
+        let mut pointers = Vec::new();
+        collect(self, &mut String::new(), &mut pointers);
+        pointers
+    }
+    
+    /// Replaces every pointer within this value.
+    pub fn map_pointers(&mut self, map: &dyn Fn(u64) -> u64) {
+        match self {
+            Value::Pointer(addr) => *addr = map(*addr),
+            Value::Struct { fields, .. } => fields.iter_mut().for_each(|(_, value)| value.map_pointers(map)),
+            Value::Array(values) => values.iter_mut().for_each(|value| value.map_pointers(map)),
+            _ => (),
+        }
+    }
+    
+    /// Converts the value to JSON; pointers become hex-strings and raw bytes arrays of numbers.
+    pub fn to_json(&self) -> serde_json::Value {
+        use serde_json::Value as Json;
+        
+        match self {
+            Value::Struct { fields, .. } => Json::Object(fields.iter()
+                .map(|(name, value)| (name.clone(), value.to_json()))
+                .collect()),
+            Value::Int(int) => Json::from(*int),
+            Value::UInt(uint) => Json::from(*uint),
+            Value::Float(float) => Json::from(*float),
+            Value::String(string) => Json::from(string.as_str()),
+            Value::Array(values) => Json::Array(values.iter().map(Value::to_json).collect()),
+            Value::Pointer(addr) => Json::from(format!("0x{addr:X?}")),
+            Value::Raw(bytes) => Json::from(bytes.as_slice()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blend_file::BlendFile;
+    
+    /// A zero-size struct `A` embedding itself as `A a[2]`, within the struct `B` of a `TEST` block.
+    fn zero_size_self_embedding() -> BlendFile<std::io::Cursor<Vec<u8>>> {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/fuzz/corpus/read_chunk_header/zero-size-self-embedding");
+        BlendFile::from_seekable(std::io::Cursor::new(std::fs::read(path).unwrap())).unwrap()
+    }
+    
+    #[test]
+    fn decode_zero_size_self_embedding() {
+        let mut file = zero_size_self_embedding();
+        let (blend, sdna, index) = (*file.header(), file.sdna().cloned().unwrap(), file.index().cloned().unwrap());
+        let decoder = Decoder::new(&blend, &sdna);
+        
+        let entry = index.find(b"TEST").unwrap();
+        let data = file.read_block(entry).unwrap();
+        
+        assert_eq!(decoder.decode_block(&entry.header, &data), Value::Struct {
+            name: String::from("B"),
+            fields: vec![
+                (String::from("c"), Value::String(String::from("abcd"))),
+                (String::from("x"), Value::Raw(Vec::new())),
+            ],
+        });
+    }
+    
+    #[test]
+    fn pointer_fields_of_zero_size_self_embedding() {
+        let file = zero_size_self_embedding();
+        let (blend, sdna) = (*file.header(), file.sdna().cloned().unwrap());
+        let decoder = Decoder::new(&blend, &sdna);
+        
+        for strct in &sdna.structs {
+            assert_eq!(decoder.pointer_fields(strct), []);
+        }
+    }
+}
@@ -0,0 +1,319 @@
+//! Comparison of two blend-files, block by block and field by field.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Seek, Write};
+
+use crate::blend_file::BlendFile;
+use crate::decode::*;
+use crate::error::UnblendError;
+use crate::pointers::*;
+use crate::renumber::Renumbering;
+use crate::sdna::Sdna;
+
+/// Raw payloads up to this size are shown as hex, larger ones only by their size.
+const MAX_HEX_LEN: usize = 32;
+
+/// The differences between two blend-files.
+///
+/// Blocks are matched by their stable key, as assigned by [`Renumbering`]:
+/// datablocks by their code and ID name, like `OB/Cube`,
+/// the blocks they point to by the path of the pointer, like `ME/Suzanne.mvert`.
+/// Pointers are compared by the key of the block they point into, not by their address.
+#[derive(Debug, Clone, Default)]
+pub struct BlendDiff {
+    /// Changes to the file-header, like the version.
+    pub header: Vec<FieldChange>,
+    pub added: Vec<DiffBlock>,
+    pub removed: Vec<DiffBlock>,
+    pub changed: Vec<BlockChanges>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DiffBlock {
+    pub key: String,
+    pub code: String,
+    /// The struct the block consists of, if known.
+    pub struct_name: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockChanges {
+    pub block: DiffBlock,
+    pub fields: Vec<FieldChange>,
+}
+
+/// A changed value, by the path of its field, like `loc[0]` or `id.name`.
+///
+/// A field only present on one side has `null` on the other.
+#[derive(Debug, Clone)]
+pub struct FieldChange {
+    pub path: String,
+    pub old: serde_json::Value,
+    pub new: serde_json::Value,
+}
+
+/// One side of a diff: every block, decoded, by its stable key.
+struct Side {
+    blocks: BTreeMap<String, (DiffBlock, Value)>,
+}
+
+impl Side {
+    fn read<R: Read + Seek>(blend_file: &mut BlendFile<R>) -> Result<Self, UnblendError> {
+        let blend = *blend_file.header();
+        let index = blend_file.index().cloned().unwrap_or_default();
+        let sdna = match blend_file.sdna().cloned() {
+            Some(sdna) => sdna,
+            None => Sdna::new(Vec::new(), Vec::new(), Vec::new(), Vec::new(), blend.usize.len() as usize)?,
+        };
+        let decoder = Decoder::new(&blend, &sdna);
+        let address_map = AddressMap::new(&index);
+        let renumbering = Renumbering::new(blend_file)?;
+        
+        // Pointers become the key of the block they point into.
+        let reference = |addr: u64| match address_map.resolve(addr) {
+            Resolution::Null => Value::String(String::from("null")),
+            Resolution::Dangling => Value::String(String::from("dangling")),
+            Resolution::Block(target) => match target.offset {
+                0 => Value::String(renumbering.block_key(target.block).to_string()),
+                offset => Value::String(format!("{}+{offset}", renumbering.block_key(target.block))),
+            },
+        };
+        
+        let mut blocks = BTreeMap::new();
+        
+        for (block, entry) in index.iter().enumerate() {
+            let header = &entry.header;
+            if header.code == b"DNA1" || header.code == b"ENDB" {
+                continue;
+            }
+            
+            let data = blend_file.read_block(entry)?;
+            let strct = decoder.block_struct(header);
+            
+            let value = match strct {
+                Some(_) => decoder.decode_block(header, &data),
+                None if address_map.is_pointer_array(&decoder, &data) => Value::Array((0..data.len())
+                    .step_by(decoder.pointer_size())
+                    .filter_map(|offset| decoder.read_pointer(&data, offset))
+                    .map(Value::Pointer)
+                    .collect()),
+                None => Value::Raw(data),
+            };
+            
+            let info = DiffBlock {
+                key: renumbering.block_key(block).to_string(),
+                code: header.code.to_string(),
+                struct_name: strct.map(|strct| strct.name.clone()),
+            };
+            
+            blocks.insert(info.key.clone(), (info, resolve_pointers(value, &reference)));
+        }
+        
+        Ok(Self { blocks })
+    }
+}
+
+/// Replaces every pointer with whatever it refers to.
+fn resolve_pointers(value: Value, reference: &dyn Fn(u64) -> Value) -> Value {
+    match value {
+        Value::Pointer(addr) => reference(addr),
+        Value::Struct { name, fields } => Value::Struct {
+            name,
+            fields: fields.into_iter().map(|(field, value)| (field, resolve_pointers(value, reference))).collect(),
+        },
+        Value::Array(values) => Value::Array(values.into_iter().map(|value| resolve_pointers(value, reference)).collect()),
+        value => value,
+    }
+}
+
+impl BlendDiff {
+    /// Decodes both indexed files thru their own DNA and compares them.
+    pub fn build<A: Read + Seek, B: Read + Seek>(old: &mut BlendFile<A>, new: &mut BlendFile<B>) -> Result<Self, UnblendError> {
+        let mut diff = Self::default();
+        
+        let (old_header, new_header) = (*old.header(), *new.header());
+        let header = [
+            ("version", old_header.version.to_string(), new_header.version.to_string()),
+            ("layout", old_header.layout.to_string(), new_header.layout.to_string()),
+            ("usize", format!("{:?}", old_header.usize), format!("{:?}", new_header.usize)),
+            ("endian", format!("{:?}", old_header.endian), format!("{:?}", new_header.endian)),
+        ];
+        
+        for (path, old, new) in header {
+            if old != new {
+                diff.header.push(FieldChange { path: path.to_string(), old: old.into(), new: new.into() });
+            }
+        }
+        
+        let old = Side::read(old)?;
+        let mut new = Side::read(new)?;
+        
+        for (key, (block, old_value)) in old.blocks {
+            let Some((_, new_value)) = new.blocks.remove(&key) else {
+                diff.removed.push(block);
+                continue;
+            };
+            
+            let mut fields = Vec::new();
+            compare(&mut String::new(), &old_value, &new_value, &mut fields);
+            
+            if !fields.is_empty() {
+                diff.changed.push(BlockChanges { block, fields });
+            }
+        }
+        
+        diff.added = new.blocks.into_values().map(|(block, _)| block).collect();
+        Ok(diff)
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.header.is_empty() && self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+    
+    /// Writes the differences as lines of text: `+` for added blocks, `-` for removed and `~` for changed ones.
+    pub fn write_text(&self, write: &mut dyn Write) -> std::io::Result<()> {
+        for change in &self.header {
+            writeln!(write, "~ header {}: {} -> {}", change.path, change.old, change.new)?;
+        }
+        
+        for block in &self.removed {
+            writeln!(write, "- {}", block.label())?;
+        }
+        
+        for block in &self.added {
+            writeln!(write, "+ {}", block.label())?;
+        }
+        
+        for changes in &self.changed {
+            writeln!(write, "~ {}", changes.block.label())?;
+            
+            for change in &changes.fields {
+                let path = match change.path.as_str() {
+                    "" => "(payload)",
+                    path => path,
+                };
+                writeln!(write, "    {path}: {} -> {}", change.old, change.new)?;
+            }
+        }
+        
+        Ok(())
+    }
+    
+    pub fn to_json(&self) -> serde_json::Value {
+        let changes = |changes: &[FieldChange]| -> Vec<serde_json::Value> {
+            changes.iter()
+                .map(|change| serde_json::json!({
+                    "path": change.path,
+                    "old": change.old,
+                    "new": change.new,
+                }))
+                .collect()
+        };
+        
+        serde_json::json!({
+            "header": changes(&self.header),
+            "added": self.added.iter().map(DiffBlock::to_json).collect::<Vec<_>>(),
+            "removed": self.removed.iter().map(DiffBlock::to_json).collect::<Vec<_>>(),
+            "changed": self.changed.iter()
+                .map(|block| {
+                    let mut json = block.block.to_json();
+                    json["fields"] = changes(&block.fields).into();
+                    json
+                })
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl DiffBlock {
+    /// The key of the block, along with its struct, like `OB/Cube (Object)`.
+    pub fn label(&self) -> String {
+        match &self.struct_name {
+            Some(struct_name) => format!("{} ({struct_name})", self.key),
+            None => format!("{} ({})", self.key, self.code),
+        }
+    }
+    
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "key": self.key,
+            "code": self.code,
+            "struct": self.struct_name,
+        })
+    }
+}
+
+/// Collects every leaf that differs between two values.
+///
+/// Structs are compared by field name and arrays by index,
+/// so that fields added or removed by another version of Blender show up as such.
+fn compare(path: &mut String, old: &Value, new: &Value, changes: &mut Vec<FieldChange>) {
+    let len = path.len();
+    
+    match (old, new) {
+        (Value::Struct { fields: old_fields, .. }, Value::Struct { fields: new_fields, .. }) => {
+            for (name, old_value) in old_fields {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(name);
+                
+                match new.field(name) {
+                    Some(new_value) => compare(path, old_value, new_value, changes),
+                    None => changes.push(FieldChange { path: path.clone(), old: leaf(old_value), new: serde_json::Value::Null }),
+                }
+                
+                path.truncate(len);
+            }
+            
+            for (name, new_value) in new_fields {
+                if old.field(name).is_none() {
+                    let path = match path.as_str() {
+                        "" => name.clone(),
+                        path => format!("{path}.{name}"),
+                    };
+                    changes.push(FieldChange { path, old: serde_json::Value::Null, new: leaf(new_value) });
+                }
+            }
+        },
+        (Value::Array(old_values), Value::Array(new_values)) => {
+            for index in 0..old_values.len().max(new_values.len()) {
+                path.push_str(&format!("[{index}]"));
+                
+                match (old_values.get(index), new_values.get(index)) {
+                    (Some(old), Some(new)) => compare(path, old, new, changes),
+                    (old, new) => changes.push(FieldChange {
+                        path: path.clone(),
+                        old: old.map(leaf).unwrap_or_default(),
+                        new: new.map(leaf).unwrap_or_default(),
+                    }),
+                }
+                
+                path.truncate(len);
+            }
+        },
+        (old, new) => if !same(old, new) {
+            changes.push(FieldChange { path: path.clone(), old: leaf(old), new: leaf(new) });
+        },
+    }
+}
+
+fn same(old: &Value, new: &Value) -> bool {
+    match (old, new) {
+        (Value::Float(old), Value::Float(new)) => old == new || (old.is_nan() && new.is_nan()),
+        // Blender may change the signedness of a field.
+        (Value::Int(_) | Value::UInt(_), Value::Int(_) | Value::UInt(_)) => match (old.as_i64(), new.as_i64()) {
+            (Some(old), Some(new)) => old == new,
+            _ => old == new,
+        },
+        (old, new) => old == new,
+    }
+}
+
+fn leaf(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Raw(bytes) if bytes.len() <= MAX_HEX_LEN => bytes.iter().map(|byte| format!("{byte:02x}")).collect::<String>().into(),
+        Value::Raw(bytes) => format!("<{} byte/s>", bytes.len()).into(),
+        value => value.to_json(),
+    }
+}
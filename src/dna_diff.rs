@@ -0,0 +1,188 @@
+//! Comparison of the DNA of two blend-files, struct by struct and field by field.
+
+use std::io::Write;
+
+use crate::sdna::*;
+
+/// The differences between two SDNAs, as when Blender is upgraded.
+///
+/// Structs are matched by name, fields by their bare identifier.
+#[derive(Debug, Clone, Default)]
+pub struct SdnaDiff {
+    pub added: Vec<StructInfo>,
+    pub removed: Vec<StructInfo>,
+    /// Structs present in both, whose size or fields changed.
+    pub changed: Vec<StructChanges>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StructInfo {
+    pub name: String,
+    pub size: u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct StructChanges {
+    pub name: String,
+    pub old_size: u16,
+    pub new_size: u16,
+    pub added_fields: Vec<FieldInfo>,
+    pub removed_fields: Vec<FieldInfo>,
+    pub retyped_fields: Vec<FieldRetype>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldInfo {
+    pub ident: String,
+    /// The full type, like `char[64]` or `ID*`.
+    pub type_string: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldRetype {
+    pub ident: String,
+    pub old_type: String,
+    pub new_type: String,
+}
+
+impl FieldInfo {
+    fn of(field: &SdnaField) -> Self {
+        Self {
+            ident: field.parsed.ident.clone(),
+            type_string: field.parsed.type_string(&field.type_name),
+        }
+    }
+}
+
+impl SdnaDiff {
+    pub fn build(old: &Sdna, new: &Sdna) -> Self {
+        let mut diff = Self::default();
+        
+        for old_struct in &old.structs {
+            let Some(new_struct) = new.struct_by_name(&old_struct.name) else {
+                diff.removed.push(StructInfo { name: old_struct.name.clone(), size: old_struct.size });
+                continue;
+            };
+            
+            let mut changes = StructChanges {
+                name: old_struct.name.clone(),
+                old_size: old_struct.size,
+                new_size: new_struct.size,
+                added_fields: Vec::new(),
+                removed_fields: Vec::new(),
+                retyped_fields: Vec::new(),
+            };
+            
+            for old_field in &old_struct.fields {
+                let old_field = FieldInfo::of(old_field);
+                
+                match new_struct.field_by_name(&old_field.ident).map(FieldInfo::of) {
+                    None => changes.removed_fields.push(old_field),
+                    Some(new_field) if new_field.type_string != old_field.type_string => changes.retyped_fields.push(FieldRetype {
+                        ident: old_field.ident,
+                        old_type: old_field.type_string,
+                        new_type: new_field.type_string,
+                    }),
+                    Some(_) => (),
+                }
+            }
+            
+            for new_field in &new_struct.fields {
+                if old_struct.field_by_name(&new_field.parsed.ident).is_none() {
+                    changes.added_fields.push(FieldInfo::of(new_field));
+                }
+            }
+            
+            if !changes.is_empty() {
+                diff.changed.push(changes);
+            }
+        }
+        
+        diff.added = new.structs.iter()
+            .filter(|strct| old.struct_by_name(&strct.name).is_none())
+            .map(|strct| StructInfo { name: strct.name.clone(), size: strct.size })
+            .collect();
+        
+        diff
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+    
+    /// Writes the differences as lines of text: `+` for added structs and fields, `-` for removed and `~` for changed ones.
+    pub fn write_text(&self, write: &mut dyn Write) -> std::io::Result<()> {
+        for strct in &self.removed {
+            writeln!(write, "- struct {} ({} bytes)", strct.name, strct.size)?;
+        }
+        
+        for strct in &self.added {
+            writeln!(write, "+ struct {} ({} bytes)", strct.name, strct.size)?;
+        }
+        
+        for strct in &self.changed {
+            match strct.old_size == strct.new_size {
+                true => writeln!(write, "~ struct {} ({} bytes)", strct.name, strct.new_size)?,
+                false => writeln!(write, "~ struct {} ({} -> {} bytes)", strct.name, strct.old_size, strct.new_size)?,
+            }
+            
+            for field in &strct.removed_fields {
+                writeln!(write, "    - {} {}", field.type_string, field.ident)?;
+            }
+            
+            for field in &strct.added_fields {
+                writeln!(write, "    + {} {}", field.type_string, field.ident)?;
+            }
+            
+            for field in &strct.retyped_fields {
+                writeln!(write, "    ~ {} -> {} {}", field.old_type, field.new_type, field.ident)?;
+            }
+        }
+        
+        Ok(())
+    }
+    
+    pub fn to_json(&self) -> serde_json::Value {
+        let structs = |structs: &[StructInfo]| -> Vec<serde_json::Value> {
+            structs.iter()
+                .map(|strct| serde_json::json!({ "name": strct.name, "size": strct.size }))
+                .collect()
+        };
+        
+        let fields = |fields: &[FieldInfo]| -> Vec<serde_json::Value> {
+            fields.iter()
+                .map(|field| serde_json::json!({ "name": field.ident, "type": field.type_string }))
+                .collect()
+        };
+        
+        serde_json::json!({
+            "added": structs(&self.added),
+            "removed": structs(&self.removed),
+            "changed": self.changed.iter()
+                .map(|strct| serde_json::json!({
+                    "name": strct.name,
+                    "old_size": strct.old_size,
+                    "new_size": strct.new_size,
+                    "added_fields": fields(&strct.added_fields),
+                    "removed_fields": fields(&strct.removed_fields),
+                    "retyped_fields": strct.retyped_fields.iter()
+                        .map(|field| serde_json::json!({
+                            "name": field.ident,
+                            "old_type": field.old_type,
+                            "new_type": field.new_type,
+                        }))
+                        .collect::<Vec<_>>(),
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl StructChanges {
+    pub fn is_empty(&self) -> bool {
+        self.old_size == self.new_size
+            && self.added_fields.is_empty()
+            && self.removed_fields.is_empty()
+            && self.retyped_fields.is_empty()
+    }
+}
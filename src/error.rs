@@ -0,0 +1,134 @@
+/// Everything that can go wrong while exploding a blend-file.
+#[derive(Debug)]
+pub enum UnblendError {
+    /// Reading the input or writing the output failed.
+    Io(std::io::Error),
+    
+    /// The file does not start with `BLENDER`.
+    BadMagic([u8; 7]),
+    
+    /// The header has a pointer-size other than `_` or `-`.
+    BadPointerSize(u8),
+    
+    /// The header has an endianess other than `v` or `V`.
+    BadEndianness(u8),
+    
+    /// The header has a malformed file-format version or version number.
+    BadVersion(Vec<u8>),
+    
+    /// The large header has a malformed `field`, like its header-size or the `-` following it.
+    BadHeader {
+        field: &'static str,
+        bytes: Vec<u8>,
+    },
+    
+    /// The input ended before the block at `offset` was fully read.
+    TruncatedBlock {
+        offset: u64,
+        code: String,
+    },
+    
+    /// The `DNA1` block can't be parsed; `offset` is relative to the start of the block.
+    MalformedDna {
+        offset: usize,
+        reason: String,
+    },
+    
+    /// The output format can't be determined from the OUT path.
+    UnknownOutputFormat(std::path::PathBuf),
+    
+    /// One of the exclusion globs can't be parsed.
+    InvalidGlob(globset::Error),
+    
+    /// The archive format can't be determined from the ARCHIVE path.
+    UnknownArchiveFormat(std::path::PathBuf),
+    
+    /// Converting to another endianness would leave this many blocks without struct in the old byte order.
+    UnswappableBlocks(usize),
+    
+    /// An entry of the archive to reassemble a blend-file from can't be used.
+    MalformedArchive {
+        entry: String,
+        reason: String,
+    },
+}
+
+impl UnblendError {
+    /// The process exit code for this error; each category gets its own.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            UnblendError::Io(_) => 1,
+            // 2 is used by clap for usage errors.
+            UnblendError::UnknownOutputFormat(_) => 3,
+            UnblendError::InvalidGlob(_) => 4,
+            UnblendError::UnknownArchiveFormat(_) => 5,
+            UnblendError::BadMagic(_) => 10,
+            UnblendError::BadPointerSize(_) => 11,
+            UnblendError::BadEndianness(_) => 12,
+            UnblendError::BadVersion(_) => 13,
+            UnblendError::BadHeader { .. } => 14,
+            UnblendError::TruncatedBlock { .. } => 20,
+            UnblendError::MalformedDna { .. } => 21,
+            UnblendError::MalformedArchive { .. } => 30,
+            UnblendError::UnswappableBlocks(_) => 40,
+        }
+    }
+    
+    pub fn malformed_archive(entry: impl Into<String>, reason: impl Into<String>) -> Self {
+        UnblendError::MalformedArchive {
+            entry: entry.into(),
+            reason: reason.into(),
+        }
+    }
+    
+    pub fn malformed_dna(offset: usize, reason: impl Into<String>) -> Self {
+        UnblendError::MalformedDna {
+            offset,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for UnblendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnblendError::Io(error) => write!(f, "I/O error: {error}"),
+            UnblendError::BadMagic(magic) => write!(f, "File is not a blend-file: Magic bytes {:?} dont match `BLENDER`", magic.escape_ascii().to_string()),
+            UnblendError::BadPointerSize(b) => write!(f, "Blend-file header has invalid pointer-size: {:?}", *b as char),
+            UnblendError::BadEndianness(b) => write!(f, "Blend-file header has invalid endianess: {:?}", *b as char),
+            UnblendError::BadVersion(bytes) => write!(f, "Blend-file header has invalid version: {:?}", bytes.escape_ascii().to_string()),
+            UnblendError::BadHeader { field, bytes } => write!(f, "Blend-file header has invalid {field}: {:?}", bytes.escape_ascii().to_string()),
+            UnblendError::TruncatedBlock { offset, code } => write!(f, "Blend-file is truncated: block `{code}` at 0x{offset:X?} is incomplete"),
+            UnblendError::MalformedDna { offset, reason } => write!(f, "DNA1 is malformed at 0x{offset:X?}: {reason}"),
+            UnblendError::UnknownOutputFormat(dst) => write!(f, "Unable to determine output format from {dst:?}"),
+            UnblendError::InvalidGlob(error) => write!(f, "Invalid exclusion glob: {error}"),
+            UnblendError::UnknownArchiveFormat(src) => write!(f, "Unable to determine archive format from {src:?}"),
+            UnblendError::MalformedArchive { entry, reason } => write!(f, "Archive entry `{entry}` is malformed: {reason}"),
+            UnblendError::UnswappableBlocks(count) => write!(f, "{count} block/s without struct can't be converted to another endianness"),
+        }
+    }
+}
+
+impl std::error::Error for UnblendError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UnblendError::Io(error) => Some(error),
+            UnblendError::InvalidGlob(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for UnblendError {
+    fn from(error: std::io::Error) -> Self {
+        // Errors raised within a `Read`-impl have to travel thru `std::io::Error`.
+        if error.get_ref().is_some_and(|inner| inner.is::<UnblendError>()) {
+            if let Ok(inner) = error.into_inner().unwrap().downcast::<UnblendError>() {
+                return *inner;
+            }
+            unreachable!()
+        }
+        
+        UnblendError::Io(error)
+    }
+}
@@ -0,0 +1,286 @@
+//! Exploding a blend-file into its many parts: a `.bin` and `.txt` file per block, and the DNA in several forms.
+
+use std::io::{Read, Seek};
+
+use crate::blend::*;
+use crate::blend_file::BlendFile;
+use crate::decode::Decoder;
+use crate::error::UnblendError;
+use crate::ids::list_ids;
+use crate::index::BlockIndex;
+use crate::naming::{name_blocks, unique};
+use crate::output::Output;
+use crate::pointers::*;
+use crate::renumber::Renumbering;
+
+/// How an indexed file is exploded, see [`explode_indexed`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExplodeOptions {
+    /// Also write every block decoded thru the DNA, as `<CODE>/<ADDR>.json`.
+    pub json: bool,
+    /// Name blocks after the datablocks they belong to, instead of their addresses.
+    pub names: bool,
+    /// Replace block addresses with stable ones, see [`Renumbering`].
+    pub stable: bool,
+}
+
+/// What was written, and what couldn't be.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExplodeStats {
+    pub blocks: usize,
+    /// Blocks that came before the `DNA1` block and could not be decoded.
+    pub undecoded: usize,
+    /// Pointers that point outside of any block.
+    pub dangling: usize,
+}
+
+/// Explodes a file in a single pass, as it is read.
+///
+/// With `json`, only blocks that come after the `DNA1` block can be decoded.
+pub fn explode<R: Read>(blend_file: BlendFile<R>, json: bool, output: &mut dyn Output) -> Result<ExplodeStats, UnblendError> {
+    explode_blocks(blend_file, json, None, None, output)
+}
+
+/// Explodes an indexed file, as opened by [`BlendFile::from_seekable`].
+///
+/// As every block is known up-front, all of them can be decoded,
+/// their pointers resolved, and the blocks named or renumbered.
+pub fn explode_indexed<R: Read + Seek>(mut blend_file: BlendFile<R>, options: &ExplodeOptions, output: &mut dyn Output) -> Result<ExplodeStats, UnblendError> {
+    let index = blend_file.index().cloned().unwrap_or_default();
+    
+    let renumbering = match options.stable {
+        true => Some(Renumbering::new(&mut blend_file)?),
+        false => None,
+    };
+    
+    let names = match (options.names, &renumbering) {
+        (true, _) => Some(name_blocks(&index, &list_ids(&mut blend_file)?)),
+        (false, Some(renumbering)) => Some(index.iter()
+            .enumerate()
+            .map(|(block, entry)| Ok(format!("{}/0x{:X?}", entry.header.code, renumbering.block_addr(block, entry.offset, entry.header.code)?)))
+            .collect::<Result<_, UnblendError>>()?),
+        (false, None) => None,
+    };
+    
+    explode_blocks(blend_file, options.json, names, renumbering, output)
+}
+
+/// Writes every block of the file to the output.
+///
+/// Blocks are written under the given `names`, in file order, or else under their address.
+/// With a `renumbering`, the pointers within blocks are rewritten accordingly.
+fn explode_blocks(
+    mut blend_file: BlendFile<impl Read>,
+    json: bool,
+    names: Option<Vec<String>>,
+    renumbering: Option<Renumbering>,
+    output: &mut dyn Output
+) -> Result<ExplodeStats, UnblendError> {
+    let blend = *blend_file.header();
+    let blend_info = format!("{blend}");
+    
+    output.write_file(
+        "blend.txt",
+        blend_info.len() as u64,
+        &mut std::io::Cursor::new(blend_info)
+    )?;
+    
+    let mut stats = ExplodeStats::default();
+    
+    // Pointers can only be resolved if all blocks are known up-front.
+    let index = blend_file.index().cloned();
+    let pointers = index.as_ref().map(|index| (index, AddressMap::new(index)));
+    
+    // Every block in file order, so that the archive fully describes the file.
+    let mut manifest = String::from("seq\tcode\taddr\tsize\tpath\n");
+    let mut used = std::collections::HashSet::new();
+    
+    while let Some(chunk_head) = blend_file.read_chunk_header()? {
+        let seq = stats.blocks;
+        stats.blocks += 1;
+        
+        let addr = match &renumbering {
+            Some(renumbering) => renumbering.block_addr(seq, blend_file.chunk_offset(), chunk_head.code)?,
+            None => chunk_head.addr,
+        };
+        
+        let path = match names.as_ref().and_then(|names| names.get(seq)) {
+            _ if chunk_head.code == b"DNA1" => String::from("DNA1"),
+            Some(name) => name.clone(),
+            None => format!("{}/0x{:X?}", chunk_head.code, addr),
+        };
+        
+        // Blender does write several blocks with the same address.
+        let path = unique(&mut used, path);
+        
+        manifest += &format!("{seq}\t{}\t0x{addr:X?}\t0x{:X?}\t{path}\n", chunk_head.code, chunk_head.size);
+        
+        if chunk_head.code == b"DNA1" {
+            output.write_file(
+                &format!("{path}.bin"),
+                chunk_head.size,
+                &mut blend_file
+            )?;
+            
+            // Time to write DNA1!
+            if let Some(sdna) = blend_file.sdna() {
+                write_dna1(&blend, sdna, output)?;
+            }
+            
+            let meta = block_meta(seq, &chunk_head, blend_file.chunk_offset(), addr);
+            
+            output.write_file(
+                &format!("{path}.txt"),
+                meta.len() as u64,
+                &mut std::io::Cursor::new(meta)
+            )?;
+            continue;
+        }
+        
+        if json && blend_file.sdna().is_none() {
+            stats.undecoded += 1;
+        }
+        
+        let decode = json && blend_file.sdna().is_some();
+        
+        if decode || renumbering.is_some() {
+            // Not `read_exact_buffer`, as a crafted size must not allocate up-front.
+            let mut data = Vec::new();
+            (&mut blend_file).take(chunk_head.size).read_to_end(&mut data)?;
+            
+            let json = match decode {
+                true => {
+                    let decoder = Decoder::new(&blend, blend_file.sdna().unwrap());
+                    let shown = BlendChunkHeader { addr, ..chunk_head };
+                    Some(block_to_json(&decoder, pointers.as_ref(), names.as_deref(), renumbering.as_ref(), &shown, &data, &mut stats.dangling))
+                },
+                false => None,
+            };
+            
+            if let Some(renumbering) = &renumbering {
+                renumbering.rewrite_block(seq, blend_file.chunk_offset(), chunk_head.code, &mut data)?;
+            }
+            
+            output.write_file(
+                &format!("{path}.bin"),
+                chunk_head.size,
+                &mut std::io::Cursor::new(&data)
+            )?;
+            
+            if let Some(json) = json {
+                output.write_file(
+                    &format!("{path}.json"),
+                    json.len() as u64,
+                    &mut std::io::Cursor::new(json)
+                )?;
+            }
+        } else {
+            output.write_file(
+                &format!("{path}.bin"),
+                chunk_head.size,
+                &mut blend_file
+            )?;
+        }
+        
+        let mut meta = block_meta(seq, &chunk_head, blend_file.chunk_offset(), addr);
+        
+        let strct = blend_file.sdna().and_then(|sdna| Decoder::new(&blend, sdna).block_struct(&chunk_head));
+        
+        if let Some(strct) = strct {
+            meta += &format!("struct\t{}\n", strct.name);
+        }
+        
+        output.write_file(
+            &format!("{path}.txt"),
+            meta.len() as u64,
+            &mut std::io::Cursor::new(meta)
+        )?;
+    }
+    
+    output.write_file(
+        "blocks.tsv",
+        manifest.len() as u64,
+        &mut std::io::Cursor::new(manifest)
+    )?;
+    
+    Ok(stats)
+}
+
+/// The metadata of a block, as written to `<CODE>/<ADDR>.txt`; `reblend` reads it back.
+///
+/// The `seq` is the position of the block within the file.
+fn block_meta(seq: usize, chunk_head: &BlendChunkHeader, offset: u64, addr: u64) -> String {
+    format!("seq\t{}\ncode\t{}\noffset\t0x{:X?}\nsize\t0x{:X?}\naddr\t0x{:X?}\nsdna\t0x{:X?}\ncount\t{}\n"
+        , seq
+        , chunk_head.code
+        , offset
+        , chunk_head.size
+        , addr
+        , chunk_head.sdna
+        , chunk_head.count
+    )
+}
+
+/// Renders a decoded block, along with its header, as a JSON document.
+///
+/// If all blocks are known, the pointers of the block are resolved too,
+/// referring to the pointed-to block by its name if there are `names`.
+/// With a `renumbering`, all pointers are shown with their new address.
+pub fn block_to_json(
+    decoder: &Decoder,
+    pointers: Option<&(&BlockIndex, AddressMap)>,
+    names: Option<&[String]>,
+    renumbering: Option<&Renumbering>,
+    chunk_head: &BlendChunkHeader,
+    data: &[u8],
+    dangling: &mut usize
+) -> String {
+    let value = decoder.decode_block(chunk_head, data);
+    let map = |addr| renumbering.map_or(addr, |renumbering| renumbering.map(addr));
+    
+    let mut shown = value.clone();
+    shown.map_pointers(&map);
+    
+    let mut json = serde_json::json!({
+        "code": chunk_head.code.to_string(),
+        "addr": format!("0x{:X?}", chunk_head.addr),
+        "struct": decoder.block_struct(chunk_head).map(|strct| strct.name.as_str()),
+        "count": chunk_head.count,
+        "data": shown.to_json(),
+    });
+    
+    if let Some((index, address_map)) = pointers {
+        let resolved: Vec<_> = address_map.resolve_value(&value)
+            .into_iter()
+            .filter_map(|(field, addr, resolution)| {
+                let mut pointer = serde_json::json!({
+                    "field": field,
+                    "addr": format!("0x{:X?}", map(addr)),
+                });
+                
+                match resolution {
+                    Resolution::Null => return None,
+                    Resolution::Block(target) => {
+                        let header = &index.entries[target.block].header;
+                        pointer["block"] = match names {
+                            Some(names) => names[target.block].clone().into(),
+                            None => format!("{}/0x{:X?}", header.code, header.addr).into(),
+                        };
+                        pointer["offset"] = target.offset.into();
+                        pointer["element"] = target.element.into();
+                    },
+                    Resolution::Dangling => {
+                        *dangling += 1;
+                        pointer["dangling"] = true.into();
+                    },
+                }
+                
+                Some(pointer)
+            })
+            .collect();
+        
+        json["pointers"] = resolved.into();
+    }
+    
+    serde_json::to_string_pretty(&json).unwrap()
+}
@@ -0,0 +1,179 @@
+//! The reference graph of a blend-file: blocks as nodes, pointers between them as edges.
+
+use std::io::{Read, Seek, Write};
+
+use crate::blend_file::BlendFile;
+use crate::decode::*;
+use crate::error::UnblendError;
+use crate::pointers::*;
+
+#[derive(Debug, Clone, Default)]
+pub struct BlockGraph {
+    /// One node per block, in file order.
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GraphNode {
+    /// Index of the block within the [`crate::BlockIndex`].
+    pub block: usize,
+    pub code: String,
+    pub addr: u64,
+    pub size: u64,
+    pub count: u64,
+    /// The struct the block consists of, if known.
+    pub struct_name: Option<String>,
+    /// The `id.name` of the block, if it is a datablock.
+    pub id_name: Option<String>,
+}
+
+/// A pointer from one block into another.
+#[derive(Debug, Clone)]
+pub struct GraphEdge {
+    pub from: usize,
+    pub to: usize,
+    /// The path of the pointer field, like `id.next` or `mat[1]`.
+    pub field: String,
+}
+
+impl BlockGraph {
+    /// Decodes every block of an indexed file and resolves its pointers.
+    ///
+    /// Without a DNA, the graph has nodes but no edges.
+    pub fn build<R: Read + Seek>(blend_file: &mut BlendFile<R>) -> Result<Self, UnblendError> {
+        let Some(index) = blend_file.index().cloned() else {
+            return Ok(Self::default())
+        };
+        
+        let blend = *blend_file.header();
+        let sdna = blend_file.sdna().cloned();
+        let decoder = sdna.as_ref().map(|sdna| Decoder::new(&blend, sdna));
+        let address_map = AddressMap::new(&index);
+        
+        let mut graph = Self::default();
+        
+        for (block, entry) in index.iter().enumerate() {
+            let header = &entry.header;
+            let mut node = GraphNode {
+                block,
+                code: header.code.to_string(),
+                addr: header.addr,
+                size: header.size,
+                count: header.count,
+                struct_name: None,
+                id_name: None,
+            };
+            
+            let decoder = decoder.as_ref().filter(|_| header.code != b"DNA1" && header.code != b"ENDB");
+            
+            if let Some(decoder) = decoder {
+                if let Some(strct) = decoder.block_struct(header) {
+                    node.struct_name = Some(strct.name.clone());
+                    
+                    let value = decoder.decode_block(header, &blend_file.read_block(entry)?);
+                    node.id_name = value.id_name().map(str::to_string);
+                    
+                    for (field, _, resolution) in address_map.resolve_value(&value) {
+                        if let Resolution::Block(target) = resolution {
+                            graph.edges.push(GraphEdge { from: block, to: target.block, field });
+                        }
+                    }
+                }
+            }
+            
+            graph.nodes.push(node);
+        }
+        
+        Ok(graph)
+    }
+    
+    /// Writes the graph in the DOT language of Graphviz.
+    pub fn write_dot(&self, write: &mut dyn Write) -> std::io::Result<()> {
+        writeln!(write, "digraph blend {{")?;
+        writeln!(write, "  node [shape=box, fontname=monospace];")?;
+        
+        for node in &self.nodes {
+            writeln!(write, "  b{} [label=\"{}\"];", node.block, escape_dot(&node.label()))?;
+        }
+        
+        for edge in &self.edges {
+            writeln!(write, "  b{} -> b{} [label=\"{}\"];", edge.from, edge.to, escape_dot(&edge.field))?;
+        }
+        
+        writeln!(write, "}}")
+    }
+    
+    /// Writes the graph as GraphML, with the block details as node attributes.
+    pub fn write_graphml(&self, write: &mut dyn Write) -> std::io::Result<()> {
+        writeln!(write, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(write, r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#)?;
+        
+        for (key, target, kind) in [
+            ("label", "node", "string"),
+            ("code", "node", "string"),
+            ("addr", "node", "string"),
+            ("size", "node", "long"),
+            ("count", "node", "long"),
+            ("struct", "node", "string"),
+            ("id", "node", "string"),
+            ("field", "edge", "string"),
+        ] {
+            writeln!(write, r#"  <key id="{key}" for="{target}" attr.name="{key}" attr.type="{kind}"/>"#)?;
+        }
+        
+        writeln!(write, r#"  <graph id="blend" edgedefault="directed">"#)?;
+        
+        for node in &self.nodes {
+            writeln!(write, r#"    <node id="b{}">"#, node.block)?;
+            writeln!(write, r#"      <data key="label">{}</data>"#, escape_xml(&node.label()))?;
+            writeln!(write, r#"      <data key="code">{}</data>"#, escape_xml(&node.code))?;
+            writeln!(write, r#"      <data key="addr">0x{:X?}</data>"#, node.addr)?;
+            writeln!(write, r#"      <data key="size">{}</data>"#, node.size)?;
+            writeln!(write, r#"      <data key="count">{}</data>"#, node.count)?;
+            if let Some(struct_name) = &node.struct_name {
+                writeln!(write, r#"      <data key="struct">{}</data>"#, escape_xml(struct_name))?;
+            }
+            if let Some(id_name) = &node.id_name {
+                writeln!(write, r#"      <data key="id">{}</data>"#, escape_xml(id_name))?;
+            }
+            writeln!(write, r#"    </node>"#)?;
+        }
+        
+        for (index, edge) in self.edges.iter().enumerate() {
+            writeln!(write, r#"    <edge id="e{index}" source="b{}" target="b{}">"#, edge.from, edge.to)?;
+            writeln!(write, r#"      <data key="field">{}</data>"#, escape_xml(&edge.field))?;
+            writeln!(write, r#"    </edge>"#)?;
+        }
+        
+        writeln!(write, r#"  </graph>"#)?;
+        writeln!(write, r#"</graphml>"#)
+    }
+}
+
+impl GraphNode {
+    /// The code and ID name, the struct, and the size of the block, one per line.
+    pub fn label(&self) -> String {
+        let mut label = match &self.id_name {
+            Some(id_name) => format!("{} {id_name}", self.code),
+            None => format!("{} 0x{:X?}", self.code, self.addr),
+        };
+        
+        if let Some(struct_name) = &self.struct_name {
+            label += &format!("\n{struct_name}");
+            if self.count != 1 {
+                label += &format!("[{}]", self.count);
+            }
+        }
+        
+        label + &format!("\n{} bytes", self.size)
+    }
+}
+
+fn escape_dot(string: &str) -> String {
+    string.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn escape_xml(string: &str) -> String {
+    string.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,103 @@
+//! The ID datablocks of a blend-file: every block with a two-letter code, like `OB` or `ME`.
+
+use std::io::{Read, Seek};
+
+use crate::blend_file::BlendFile;
+use crate::decode::*;
+use crate::error::UnblendError;
+use crate::pointers::*;
+
+/// The `ID` struct at the start of a datablock.
+#[derive(Debug, Clone)]
+pub struct IdBlock {
+    /// Index of the block within the [`crate::BlockIndex`].
+    pub block: usize,
+    pub code: String,
+    pub addr: u64,
+    /// The `ID.name`, without the two-letter code it starts with.
+    pub name: String,
+    /// The `ID.us` user count.
+    pub users: i64,
+    pub flag: i64,
+    pub tag: i64,
+    /// The `ID.lib` pointer; non-null if the datablock is linked from another file.
+    pub lib: u64,
+    /// The file-path (or name) of the library the datablock is linked from, if it could be resolved.
+    pub library: Option<String>,
+}
+
+impl IdBlock {
+    pub fn is_linked(&self) -> bool {
+        self.lib != 0
+    }
+    
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "code": self.code,
+            "addr": format!("0x{:X?}", self.addr),
+            "name": self.name,
+            "users": self.users,
+            "flag": self.flag,
+            "tag": self.tag,
+            "lib": format!("0x{:X?}", self.lib),
+            "library": self.library,
+        })
+    }
+}
+
+/// Decodes the `ID` of every datablock of an indexed file, in file order.
+///
+/// Blocks whose struct is unknown, or doesn't start with an `id` field, are skipped.
+pub fn list_ids<R: Read + Seek>(blend_file: &mut BlendFile<R>) -> Result<Vec<IdBlock>, UnblendError> {
+    let (Some(index), Some(sdna)) = (blend_file.index().cloned(), blend_file.sdna().cloned()) else {
+        return Ok(Vec::new())
+    };
+    
+    let blend = *blend_file.header();
+    let decoder = Decoder::new(&blend, &sdna);
+    let address_map = AddressMap::new(&index);
+    
+    let mut ids = Vec::new();
+    
+    for (block, entry) in index.iter().enumerate() {
+        if !entry.header.code.is_id() || decoder.block_struct(&entry.header).is_none() {
+            continue;
+        }
+        
+        let value = decoder.decode_block(&entry.header, &blend_file.read_block(entry)?);
+        let Some(id) = value.field("id") else {
+            continue;
+        };
+        
+        let int = |ident| id.field(ident).and_then(Value::as_i64).unwrap_or(0);
+        let lib = match id.field("lib") {
+            Some(Value::Pointer(addr)) => *addr,
+            _ => 0,
+        };
+        
+        // The library is itself a datablock, naming the file it stands for.
+        let library = match address_map.resolve(lib) {
+            Resolution::Block(target) => {
+                let entry = &index.entries[target.block];
+                let value = decoder.decode_block(&entry.header, &blend_file.read_block(entry)?);
+                let path = value.field("filepath").or(value.field("name")).and_then(Value::as_str);
+                path.or(value.id_name()).map(str::to_string)
+            },
+            _ => None,
+        };
+        
+        ids.push(IdBlock {
+            block,
+            code: entry.header.code.to_string(),
+            addr: entry.header.addr,
+            name: value.id_name().unwrap_or_default().to_string(),
+            users: int("us"),
+            flag: int("flag"),
+            tag: int("tag"),
+            lib,
+            library,
+        });
+    }
+    
+    Ok(ids)
+}
@@ -0,0 +1,119 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::blend::*;
+use crate::error::UnblendError;
+
+/// Where a block is located within the (decompressed) file.
+#[derive(Debug, Clone)]
+pub struct BlockIndexEntry {
+    pub header: BlendChunkHeader,
+    /// Offset of the block-header.
+    pub offset: u64,
+    /// Offsets of the first and one-past-the-last byte of the payload.
+    pub payload: std::ops::Range<u64>,
+}
+
+/// The location of every block of a file, in file order.
+#[derive(Debug, Clone, Default)]
+pub struct BlockIndex {
+    pub entries: Vec<BlockIndexEntry>,
+}
+
+impl BlockIndex {
+    /// Scans the block-headers of a file, seeking over the payloads.
+    /// 
+    /// The source must be positioned at the first block-header, at `offset`.
+    /// Scanning stops after the `ENDB` block; an incomplete block fails with [`UnblendError::TruncatedBlock`],
+    /// so that everything built on the index sees either the whole file or an error.
+    pub fn scan(blend: &BlendHeader, read: &mut (impl Read + Seek), offset: u64) -> Result<Self, UnblendError> {
+        let file_len = read.seek(SeekFrom::End(0))?;
+        read.seek(SeekFrom::Start(offset))?;
+        
+        let mut entries = Vec::new();
+        let mut offset = offset;
+        
+        loop {
+            let header = match read_chunk_header(blend, read) {
+                Ok(header) => header,
+                Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Err(UnblendError::TruncatedBlock {
+                    offset,
+                    code: String::from("?"),
+                }),
+                Err(err) => return Err(err.into()),
+            };
+            
+            let start = offset + blend.chunk_header_len();
+            let end = match start.checked_add(header.size) {
+                Some(end) if end <= file_len => end,
+                _ => return Err(UnblendError::TruncatedBlock {
+                    offset,
+                    code: header.code.to_string(),
+                }),
+            };
+            
+            entries.push(BlockIndexEntry {
+                header,
+                offset,
+                payload: start..end,
+            });
+            
+            if header.code == b"ENDB" {
+                break;
+            }
+            
+            read.seek(SeekFrom::Start(end))?;
+            offset = end;
+        }
+        
+        Ok(Self { entries })
+    }
+    
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    
+    pub fn iter(&self) -> std::slice::Iter<'_, BlockIndexEntry> {
+        self.entries.iter()
+    }
+    
+    /// The first block with the given code.
+    pub fn find(&self, code: &[u8; 4]) -> Option<&BlockIndexEntry> {
+        self.entries.iter().find(|entry| entry.header.code == code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn scan(name: &str) -> Result<BlockIndex, UnblendError> {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/read_chunk_header").join(name);
+        let mut read = std::io::Cursor::new(std::fs::read(path).unwrap());
+        let blend = read_header(&mut read)?;
+        BlockIndex::scan(&blend, &mut read, blend.layout.header_len())
+    }
+    
+    fn truncated(result: Result<BlockIndex, UnblendError>) -> Option<(u64, String)> {
+        match result {
+            Err(UnblendError::TruncatedBlock { offset, code }) => Some((offset, code)),
+            _ => None,
+        }
+    }
+    
+    #[test]
+    fn scan_complete_file() {
+        let index = scan("legacy-le64").unwrap();
+        assert_eq!(index.iter().last().unwrap().header.code, BlendChunkCode(*b"ENDB"));
+    }
+    
+    #[test]
+    fn fail_on_incomplete_blocks() {
+        assert_eq!(truncated(scan("truncated-block")), Some((0xA4, String::from("ME"))));
+        assert_eq!(truncated(scan("huge-block-size")), Some((0x11, String::from("DATA"))));
+        assert_eq!(truncated(scan("header-only")), Some((0xC, String::from("?"))));
+    }
+}
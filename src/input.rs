@@ -1,17 +1,158 @@
-pub fn select_input(src: &std::path::PathBuf) -> std::io::BufReader<Box<dyn std::io::Read>> {
+use std::io::{Read, Seek};
+
+use crate::zstd_seekable::*;
+use crate::error::UnblendError;
+
+pub type InputBox = std::io::BufReader<Box<dyn std::io::Read>>;
+
+pub fn select_input(src: &std::path::PathBuf) -> Result<InputBox, UnblendError> {
     
     let boxed: Box<dyn std::io::Read> = if src == std::path::Path::new("-") {
-        eprintln!("Reading blend from STDIN.");
+        log::info!("Reading blend from STDIN.");
         Box::new(std::io::stdin().lock())
     } else {
-        eprintln!("Reading blend from {src:?}.");
-        let file = match std::fs::File::open(src) {
-            Ok(file) => file,
-            Err(err) => panic!("Failed to open blend-file: {err}"),
-        };
+        log::info!("Reading blend from {src:?}.");
+        let mut file = std::fs::File::open(src)?;
         
-        Box::new(file)
+        match open_seekable_zstd(&mut file)? {
+            Some(frames) => {
+                log::info!("Decompressing blend as seekable ZSTD of {} frame/s.", frames.len());
+                Box::new(SeekableZstd::new(file, frames))
+            },
+            None => Box::new(file),
+        }
     };
     
-    std::io::BufReader::new(boxed)
+    decompress_input(std::io::BufReader::new(boxed))
+}
+
+/// Like `Read + Seek`, but usable as a trait-object.
+pub trait ReadSeek: Read + Seek {}
+impl<T: Read + Seek> ReadSeek for T {}
+
+pub type SeekableInputBox = std::io::BufReader<Box<dyn ReadSeek>>;
+
+/// Like [`select_input`], but for random access.
+/// 
+/// Inputs that can't be seeked in, like STDIN or gzip-streams,
+/// are decompressed and spooled to a temporary file first.
+pub fn select_seekable_input(src: &std::path::PathBuf) -> Result<SeekableInputBox, UnblendError> {
+    if let Some(input) = open_seekable_input(src)? {
+        return Ok(input);
+    }
+    
+    let mut input = select_input(src)?;
+    let mut spool = tempfile::tempfile()?;
+    let len = std::io::copy(&mut input, &mut spool)?;
+    spool.rewind()?;
+    
+    log::info!("Spooled {len} byte/s of blend to a temporary file.");
+    Ok(std::io::BufReader::new(Box::new(spool)))
+}
+
+/// Opens the input for random access, if that's possible without spooling it:
+/// uncompressed files are memory-mapped, seekable zstd-files decompressed as needed.
+pub fn open_seekable_input(src: &std::path::PathBuf) -> Result<Option<SeekableInputBox>, UnblendError> {
+    if src == std::path::Path::new("-") {
+        return Ok(None);
+    }
+    
+    let mut file = std::fs::File::open(src)?;
+    
+    if let Some(frames) = open_seekable_zstd(&mut file)? {
+        log::info!("Reading blend from {src:?}.");
+        log::info!("Decompressing blend as seekable ZSTD of {} frame/s.", frames.len());
+        return Ok(Some(std::io::BufReader::new(Box::new(SeekableZstd::new(file, frames)))));
+    }
+    
+    let mut magic = [0u8; 7];
+    let len = file.by_ref().take(7).read(&mut magic)?;
+    file.rewind()?;
+    
+    if len == 7 && &magic == b"BLENDER" && file.metadata()?.is_file() {
+        log::info!("Reading blend from {src:?} as memory-map.");
+        // SAFETY: The file must not be modified while it is mapped,
+        // which is no different from any other program reading a blend-file.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        return Ok(Some(std::io::BufReader::new(Box::new(std::io::Cursor::new(map)))));
+    }
+    
+    Ok(None)
+}
+
+/// Reads the seek table of the given file, if it is a zstd-file in the seekable format.
+fn open_seekable_zstd(file: &mut std::fs::File) -> std::io::Result<Option<Vec<SeekFrame>>> {
+    let mut magic = [0u8; 4];
+    let len = file.by_ref().take(4).read(&mut magic)?;
+    file.rewind()?;
+    
+    if len < 4 || magic != ZSTD_MAGIC {
+        return Ok(None)
+    }
+    
+    read_seek_table(file)
+}
+
+/// Sniffs the magic bytes of the input and, if it is compressed, wraps it in the matching decoder.
+pub fn decompress_input(mut input: InputBox) -> Result<InputBox, UnblendError> {
+    // A single read may return less than the magic bytes, as from a pipe.
+    let mut magic = Vec::with_capacity(ZSTD_MAGIC.len());
+    (&mut input).take(ZSTD_MAGIC.len() as u64).read_to_end(&mut magic)?;
+    
+    let (is_gzip, is_zstd) = (magic.starts_with(&GZIP_MAGIC), magic.starts_with(&ZSTD_MAGIC));
+    let input: InputBox = std::io::BufReader::new(Box::new(std::io::Cursor::new(magic).chain(input)));
+    
+    if is_gzip {
+        log::info!("Decompressing blend as GZIP.");
+        return Ok(std::io::BufReader::new(Box::new(flate2::read::GzDecoder::new(input))));
+    }
+    
+    if is_zstd {
+        log::info!("Decompressing blend as ZSTD.");
+        let decoder = zstd::stream::read::Decoder::with_buffer(input)?;
+        return Ok(std::io::BufReader::new(Box::new(decoder)));
+    }
+    
+    Ok(input)
+}
+
+/// Magic bytes of a gzip-stream, as written by Blender's "Compress" option before 3.0.
+pub const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+/// Magic bytes of a zstd-frame, as written by Blender's "Compress" option since 3.0.
+pub const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Returns a single byte per read, like a slow pipe.
+    struct Trickle(std::io::Cursor<Vec<u8>>);
+    
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(1);
+            self.0.read(&mut buf[..len])
+        }
+    }
+    
+    fn decompress(data: Vec<u8>) -> Vec<u8> {
+        let input: InputBox = std::io::BufReader::new(Box::new(Trickle(std::io::Cursor::new(data))));
+        let mut decompressed = Vec::new();
+        decompress_input(input).unwrap().read_to_end(&mut decompressed).unwrap();
+        decompressed
+    }
+    
+    #[test]
+    fn sniff_magic_from_trickling_input() {
+        let blend = b"BLENDER-v300";
+        
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut gzip, blend).unwrap();
+        
+        assert_eq!(decompress(gzip.finish().unwrap()), blend);
+        assert_eq!(decompress(zstd::bulk::compress(blend, 0).unwrap()), blend);
+        assert_eq!(decompress(blend.to_vec()), blend);
+        assert_eq!(decompress(b"BL".to_vec()), b"BL");
+    }
 }
@@ -0,0 +1,64 @@
+//! Parsing of Blender's `.blend` files, as used by the `unblend` program.
+//!
+//! Start by opening a [`BlendFile`] from any `Read` or `Read + Seek` source.
+
+pub mod read_ext;
+pub mod byte_ext;
+
+pub mod blend;
+pub use blend::{BlendHeader, BlendChunkHeader};
+
+pub mod sdna;
+pub use sdna::{Sdna, SdnaStruct, SdnaField, SdnaType, FieldName};
+
+pub mod decode;
+pub use decode::{Decoder, Value};
+
+pub mod pointers;
+pub use pointers::{AddressMap, Resolution, PointerTarget};
+
+pub mod ids;
+pub use ids::IdBlock;
+
+pub mod naming;
+
+pub mod renumber;
+pub use renumber::Renumbering;
+
+pub mod graph;
+pub use graph::{BlockGraph, GraphNode, GraphEdge};
+
+pub mod blend_file;
+pub use blend_file::BlendFile;
+
+pub mod index;
+pub use index::{BlockIndex, BlockIndexEntry};
+
+pub mod blocks;
+pub use blocks::{Blocks, Block};
+
+pub mod zstd_seekable;
+
+pub mod input;
+
+pub mod output;
+
+pub mod explode;
+pub use explode::{ExplodeOptions, ExplodeStats};
+
+pub mod reblend;
+
+pub mod convert;
+pub use convert::ConvertTarget;
+
+pub mod diff;
+pub use diff::BlendDiff;
+
+pub mod dna_diff;
+pub use dna_diff::SdnaDiff;
+
+pub mod codegen;
+pub use codegen::CodegenLang;
+
+pub mod error;
+pub use error::UnblendError;
@@ -1,29 +1,69 @@
+use std::io::Write;
 use std::path::PathBuf;
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 
-mod read_ext;
-mod byte_ext;
-
-mod blend;
-use blend::*;
-
-mod input;
-use input::*;
-
-mod output;
-use output::*;
-
-use crate::read_ext::ReadExt;
+use unblend::input::*;
+use unblend::output::*;
+use unblend::error::*;
+use unblend::BlendFile;
+use unblend::explode::{explode, explode_indexed, ExplodeOptions};
+use unblend::graph::BlockGraph;
+use unblend::ids::list_ids;
+use unblend::reblend::*;
+use unblend::convert::ConvertTarget;
+use unblend::diff::BlendDiff;
+use unblend::dna_diff::SdnaDiff;
+use unblend::codegen::{CodegenLang, write_code};
 
 /// A program to explode blend files into their many parts.
+/// 
+/// Without a subcommand, FILE is exploded into OUT.
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about)] // Read from `Cargo.toml`
+#[command(subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    
+    #[command(flatten)]
+    explode: Blend2Zip,
+    
+    /// Only print warnings and errors, not the progress.
+    #[arg(short='q',long="quiet",global = true)]
+    quiet: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Export the graph of pointers between blocks, as DOT or GraphML.
+    Graph(GraphArgs),
+    
+    /// List every ID datablock, with its name, users and library.
+    Ids(IdsArgs),
+    
+    /// Reassemble a blend-file from an exploded archive.
+    Reblend(ReblendArgs),
+    
+    /// Convert a blend-file to another pointer-size and endianness.
+    Convert(ConvertArgs),
+    
+    /// Compare two blend-files, datablock by datablock and field by field.
+    Diff(DiffArgs),
+    
+    /// Compare the DNA of two blend-files, struct by struct and field by field.
+    DnaDiff(DiffArgs),
+    
+    /// Generate source code mirroring the structs of a blend-file's DNA.
+    Codegen(CodegenArgs),
+}
+
+#[derive(Debug, Args)]
 struct Blend2Zip {
     /// The `.blend`-file to explode into parts.
     /// 
     /// By specifying `-` as FILE, reading from STDIN is supported.
-    #[arg(value_name = "FILE")]
-    src: PathBuf,
+    #[arg(value_name = "FILE", required = true)]
+    src: Option<PathBuf>,
     
     /// Where to write the exploded blend-file parts to.
     /// 
@@ -34,108 +74,407 @@ struct Blend2Zip {
     /// - `tar` writes a tape-archive.
     /// 
     /// By specifying `-` as OUT, writing to STDOUT as TAR is supported.
-    #[arg(value_name = "OUT")]
-    dst: PathBuf,
+    #[arg(value_name = "OUT", required = true)]
+    dst: Option<PathBuf>,
     
     /// Exclude files from being emitted via globs.
     /// 
     /// Uses <https://crates.io/crates/globset> internally.
     #[arg(short='x',long="exclude",value_name = "GLOB")]
     excludes: Vec<String>,
+    
+    /// Also write every block decoded thru the DNA, as `<CODE>/<ADDR>.json`.
+    /// 
+    /// Only blocks that come after the `DNA1` block can be decoded,
+    /// unless it was located first (see `--two-pass`).
+    #[arg(short='j',long="json")]
+    json: bool,
+    
+    /// Locate the `DNA1` block before writing any other block.
+    /// 
+    /// This is always done for uncompressed files and seekable zstd-files;
+    /// other inputs, like STDIN or gzip-streams, are spooled to a temporary file first.
+    /// 
+    /// Every block is then annotated with its struct name and,
+    /// with `--json`, decoded.
+    #[arg(short='2',long="two-pass")]
+    two_pass: bool,
+    
+    /// Name blocks after the datablocks they belong to, instead of their addresses.
+    /// 
+    /// ID blocks are written as `<CODE>/<NAME>`, like `OB/Cube.bin`,
+    /// and the `DATA` blocks following them as `<CODE>/<NAME>/DATA/<N>`.
+    /// Other blocks are numbered per code, like `REND/0.bin`.
+    /// 
+    /// Implies `--two-pass`.
+    #[arg(short='n',long="names")]
+    names: bool,
+    
+    /// Replace block addresses with stable ones, derived from the datablocks referencing them.
+    /// 
+    /// Pointers within the `.bin`, `.txt` and `.json` files are rewritten the same way,
+    /// so that saving an unchanged file again yields an identical archive.
+    /// Pointers that point outside of any block become null.
+    /// 
+    /// Implies `--two-pass`.
+    #[arg(short='s',long="stable")]
+    stable: bool,
+}
+
+#[derive(Debug, Args)]
+struct GraphArgs {
+    /// The `.blend`-file to read; `-` reads from STDIN.
+    #[arg(value_name = "FILE")]
+    src: PathBuf,
+    
+    /// Where to write the graph to.
+    /// 
+    /// The file-extension determines the format:
+    /// 
+    /// - `dot` or `gv` writes the DOT language of Graphviz.
+    /// 
+    /// - `graphml` writes GraphML.
+    /// 
+    /// By specifying `-` as OUT, writing to STDOUT as DOT is supported.
+    #[arg(value_name = "OUT")]
+    dst: PathBuf,
+}
+
+#[derive(Debug, Args)]
+struct IdsArgs {
+    /// The `.blend`-file to read; `-` reads from STDIN.
+    #[arg(value_name = "FILE")]
+    src: PathBuf,
+    
+    /// Only list datablocks with the given two-letter code, like `OB` or `MA`.
+    #[arg(short='t',long="type",value_name = "CODE")]
+    types: Vec<String>,
+    
+    /// Print a JSON array instead of a table.
+    #[arg(short='j',long="json")]
+    json: bool,
+}
+
+#[derive(Debug, Args)]
+struct ReblendArgs {
+    /// The archive to reassemble, as written by exploding a blend-file.
+    /// 
+    /// The file-extension determines the format, either `zip` or `tar`.
+    /// By specifying `-` as ARCHIVE, reading a TAR from STDIN is supported.
+    #[arg(value_name = "ARCHIVE")]
+    src: PathBuf,
+    
+    /// Where to write the `.blend`-file to; `-` writes to STDOUT.
+    #[arg(value_name = "OUT")]
+    dst: PathBuf,
+}
+
+#[derive(Debug, Args)]
+struct ConvertArgs {
+    /// The pointer-size and endianness to convert to: `32le`, `32be`, `64le` or `64be`.
+    #[arg(long="to",value_name = "TARGET")]
+    to: ConvertTarget,
+    
+    /// Copy blocks without struct as-is when changing the endianness, instead of failing.
+    /// 
+    /// Their bytes can't be swapped, as nothing tells what they consist of.
+    #[arg(long="keep-raw")]
+    keep_raw: bool,
+    
+    /// The `.blend`-file to convert; `-` reads from STDIN.
+    #[arg(value_name = "FILE")]
+    src: PathBuf,
+    
+    /// Where to write the converted `.blend`-file to; `-` writes to STDOUT.
+    #[arg(value_name = "OUT")]
+    dst: PathBuf,
+}
+
+#[derive(Debug, Args)]
+struct DiffArgs {
+    /// The old `.blend`-file.
+    #[arg(value_name = "OLD")]
+    old: PathBuf,
+    
+    /// The new `.blend`-file.
+    #[arg(value_name = "NEW")]
+    new: PathBuf,
+    
+    /// Print a JSON report instead of text.
+    #[arg(short='j',long="json")]
+    json: bool,
+}
+
+#[derive(Debug, Args)]
+struct CodegenArgs {
+    /// The language to generate: `rust`, `c` or `ksy` (Kaitai Struct).
+    #[arg(long="lang",value_name = "LANG")]
+    lang: CodegenLang,
+    
+    /// The `.blend`-file whose DNA to read; `-` reads from STDIN.
+    #[arg(value_name = "FILE")]
+    src: PathBuf,
+    
+    /// Where to write the code to; defaults to STDOUT.
+    #[arg(short='o',long="output",value_name = "OUT")]
+    dst: Option<PathBuf>,
+}
+
+fn main() -> std::process::ExitCode {
+    let cli = Cli::parse();
+    
+    log::set_logger(&StderrLogger).expect("no other logger is set");
+    log::set_max_level(match cli.quiet {
+        true => log::LevelFilter::Warn,
+        false => log::LevelFilter::Info,
+    });
+    
+    let result = match cli.command {
+        Some(Command::Graph(args)) => graph(args),
+        Some(Command::Ids(args)) => ids(args),
+        Some(Command::Reblend(args)) => reblend(args),
+        Some(Command::Convert(args)) => convert(args),
+        Some(Command::Diff(args)) => diff(args),
+        Some(Command::DnaDiff(args)) => dna_diff(args),
+        Some(Command::Codegen(args)) => codegen(args),
+        None => run(cli.explode),
+    };
+    
+    match result {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        // The reader went away, as `head` does once it has seen enough.
+        Err(UnblendError::Io(err)) if err.kind() == std::io::ErrorKind::BrokenPipe => std::process::ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("ERROR: {err}");
+            std::process::ExitCode::from(err.exit_code())
+        }
+    }
 }
 
-fn main() {
-    let args = Blend2Zip::parse();
-    run(args).unwrap();
+/// Prints the diagnostics of the library to STDERR, as the CLI prints its own.
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+    
+    fn log(&self, record: &log::Record) {
+        match record.level() {
+            log::Level::Error => eprintln!("ERROR: {}", record.args()),
+            log::Level::Warn => eprintln!("WARNING: {}", record.args()),
+            _ => eprintln!("{}", record.args()),
+        }
+    }
+    
+    fn flush(&self) {}
 }
 
-fn run(args: Blend2Zip) -> std::io::Result<()> {
-    let mut input = select_input(&args.src);
-    let mut output = select_output(&args.dst);
+fn run(args: Blend2Zip) -> Result<(), UnblendError> {
+    // Both are required, unless a subcommand is given.
+    let (src, dst) = (args.src.clone().unwrap(), args.dst.clone().unwrap());
+    let mut output = select_output(&dst)?;
     
-    if let Some(globber) = build_globber(args.excludes) {
+    if let Some(globber) = build_globber(args.excludes.clone())? {
         output = Box::new(OutputGlobber {
             globset: globber,
             output,
         });
     }
     
+    let seekable = match args.two_pass || args.names || args.stable {
+        true => Some(select_seekable_input(&src)?),
+        false => open_seekable_input(&src)?,
+    };
+    
+    let stats = match seekable {
+        Some(input) => explode_indexed(BlendFile::from_seekable(input)?, &ExplodeOptions {
+            json: args.json,
+            names: args.names,
+            stable: args.stable,
+        }, output.as_mut())?,
+        None => explode(BlendFile::from_reader(select_input(&src)?)?, args.json, output.as_mut())?,
+    };
+    
+    output.finish()?;
+    
+    if stats.undecoded > 0 {
+        log::warn!("{} block/s came before the DNA1 block and could not be decoded.", stats.undecoded);
+    }
+    
+    if stats.dangling > 0 {
+        log::warn!("{} pointer/s point outside of any block.", stats.dangling);
+    }
+    
+    log::info!("Exploded {} block/s.", stats.blocks);
+    Ok(())
+}
+
+fn graph(args: GraphArgs) -> Result<(), UnblendError> {
+    let format = match args.dst.extension().and_then(|ext| ext.to_str()) {
+        _ if args.dst.as_os_str() == "-" => "dot",
+        Some("dot" | "gv") => "dot",
+        Some("graphml") => "graphml",
+        _ => return Err(UnblendError::UnknownOutputFormat(args.dst))
+    };
+    
+    let mut blend_file = BlendFile::from_seekable(select_seekable_input(&args.src)?)?;
+    let graph = BlockGraph::build(&mut blend_file)?;
+    
+    let mut output: Box<dyn std::io::Write> = match args.dst.as_os_str() == "-" {
+        true => Box::new(std::io::stdout().lock()),
+        false => Box::new(std::fs::File::create(&args.dst)?),
+    };
+    let mut output = std::io::BufWriter::new(output.as_mut());
     
-    let blend = read_header(&mut input)?;
-    let blend_info = format!("{blend}");
+    match format {
+        "graphml" => graph.write_graphml(&mut output)?,
+        _ => graph.write_dot(&mut output)?,
+    }
+    
+    output.flush()?;
+    log::info!("Wrote {} node/s and {} edge/s.", graph.nodes.len(), graph.edges.len());
+    Ok(())
+}
+
+fn ids(args: IdsArgs) -> Result<(), UnblendError> {
+    let mut blend_file = BlendFile::from_seekable(select_seekable_input(&args.src)?)?;
     
-    output.write_file(
-        "blend.txt",
-        blend_info.len() as u64,
-        &mut std::io::Cursor::new(blend_info)
-    ).unwrap();
+    let ids: Vec<_> = list_ids(&mut blend_file)?
+        .into_iter()
+        .filter(|id| args.types.is_empty() || args.types.iter().any(|code| code.eq_ignore_ascii_case(&id.code)))
+        .collect();
     
-    loop {
-        let chunk_head = match read_chunk_header(&blend, &mut input) {
-            Ok(chunk) => chunk,
-            Err(err) => panic!("Failed to read chunk header: {err:?}")
+    let mut out = std::io::stdout().lock();
+    
+    if args.json {
+        let json: Vec<_> = ids.iter().map(|id| id.to_json()).collect();
+        writeln!(out, "{}", serde_json::to_string_pretty(&json).unwrap())?;
+        return Ok(())
+    }
+    
+    let width = ids.iter().map(|id| id.name.chars().count()).max().unwrap_or(0).max(4);
+    writeln!(out, "CODE  {:width$}  {:>5}  {:>6}  {:>10}  LIB", "NAME", "USERS", "FLAG", "TAG")?;
+    
+    for id in &ids {
+        let lib = match (&id.library, id.is_linked()) {
+            (Some(library), _) => library.clone(),
+            (None, true) => format!("0x{:X?}", id.lib),
+            (None, false) => String::from("-"),
         };
         
-        //eprintln!("Parsed chunk: {chunk_head}");
-        
-        if chunk_head.code == b"DNA1" {
-            let dna1 = input.read_exact_buffer(chunk_head.size as usize)?;
-            
-            output.write_file(
-                "DNA1.bin",
-                dna1.len() as u64,
-                &mut std::io::Cursor::new(&dna1)
-            ).unwrap();
-            
-            // Time to parse DNA1!
-            read_dna1(&blend, dna1, output.as_mut())?;
-            continue;
-        }
-        
-        let path = format!("{}/0x{:X?}", chunk_head.code, chunk_head.addr);
-        
-        output.write_file(
-            &format!("{path}.bin"),
-            chunk_head.size as u64,
-            &mut input.take_borrowed(chunk_head.size as usize)
-        ).unwrap();
-        
-        let meta = format!("code\t{}\nsize\t0x{:X?}\naddr\t0x{:X?}\nsdna\t0x{:X?}\ncount\t{}\n"
-            , chunk_head.code
-            , chunk_head.size
-            , chunk_head.addr
-            , chunk_head.sdna
-            , chunk_head.count
-        );
-        
-        output.write_file(
-            &format!("{path}.txt"),
-            meta.len() as u64,
-            &mut std::io::Cursor::new(meta)
-        ).unwrap();
-        
-        if chunk_head.code == b"ENDB" {
-            eprintln!("Reached ENDB chunk.");
-            break;
-        }
+        writeln!(out, "{:4}  {:width$}  {:>5}  {:>6}  {:>10}  {lib}", id.code, id.name, id.users, id.flag, id.tag)?;
+    }
+    
+    log::info!("Listed {} datablock/s.", ids.len());
+    Ok(())
+}
+
+fn reblend(args: ReblendArgs) -> Result<(), UnblendError> {
+    let output: Box<dyn std::io::Write> = match args.dst.as_os_str() == "-" {
+        true => Box::new(std::io::stdout().lock()),
+        false => Box::new(std::fs::File::create(&args.dst)?),
+    };
+    
+    let mut reblend = Reblend::new(std::io::BufWriter::new(output));
+    for_each_entry(&args.src, &mut |path, data| reblend.add_entry(path, data))?;
+    
+    let blocks = reblend.finish()?;
+    log::info!("Wrote {blocks} block/s.");
+    Ok(())
+}
+
+fn convert(args: ConvertArgs) -> Result<(), UnblendError> {
+    let mut blend_file = BlendFile::from_seekable(select_seekable_input(&args.src)?)?;
+    
+    // Converted into a temporary file first, so that a failure leaves no output behind.
+    let mut converted = tempfile::tempfile()?;
+    let stats = unblend::convert::convert(&mut blend_file, args.to, args.keep_raw, &mut std::io::BufWriter::new(&mut converted))?;
+    
+    let mut output: Box<dyn std::io::Write> = match args.dst.as_os_str() == "-" {
+        true => Box::new(std::io::stdout().lock()),
+        false => Box::new(std::fs::File::create(&args.dst)?),
+    };
+    
+    std::io::Seek::rewind(&mut converted)?;
+    std::io::copy(&mut converted, &mut output)?;
+    output.flush()?;
+    
+    if stats.raw > 0 && blend_file.header().endian != args.to.endian {
+        log::warn!("{} block/s without struct were copied without swapping their bytes.", stats.raw);
+    }
+    
+    log::info!("Converted {} of {} block/s.", stats.converted, stats.blocks);
+    Ok(())
+}
+
+fn diff(args: DiffArgs) -> Result<(), UnblendError> {
+    let mut old = BlendFile::from_seekable(select_seekable_input(&args.old)?)?;
+    let mut new = BlendFile::from_seekable(select_seekable_input(&args.new)?)?;
+    let diff = BlendDiff::build(&mut old, &mut new)?;
+    
+    let mut out = std::io::stdout().lock();
+    
+    if args.json {
+        writeln!(out, "{}", serde_json::to_string_pretty(&diff.to_json()).unwrap())?;
+    } else {
+        diff.write_text(&mut out)?;
     }
     
-    output.finish();
+    log::info!("{} block/s added, {} removed and {} changed.", diff.added.len(), diff.removed.len(), diff.changed.len());
+    Ok(())
+}
+
+fn dna_diff(args: DiffArgs) -> Result<(), UnblendError> {
+    let read_sdna = |path: &PathBuf| -> Result<_, UnblendError> {
+        let blend_file = BlendFile::from_seekable(select_seekable_input(path)?)?;
+        blend_file.sdna().cloned().ok_or_else(|| UnblendError::malformed_dna(0, "the file has no DNA1 block"))
+    };
+    
+    let diff = SdnaDiff::build(&read_sdna(&args.old)?, &read_sdna(&args.new)?);
+    
+    let mut out = std::io::stdout().lock();
+    
+    if args.json {
+        writeln!(out, "{}", serde_json::to_string_pretty(&diff.to_json()).unwrap())?;
+    } else {
+        diff.write_text(&mut out)?;
+    }
+    
+    log::info!("{} struct/s added, {} removed and {} changed.", diff.added.len(), diff.removed.len(), diff.changed.len());
+    Ok(())
+}
+
+fn codegen(args: CodegenArgs) -> Result<(), UnblendError> {
+    let blend_file = BlendFile::from_seekable(select_seekable_input(&args.src)?)?;
+    let sdna = blend_file.sdna()
+        .ok_or_else(|| UnblendError::malformed_dna(0, "the file has no DNA1 block"))?;
+    
+    let output: Box<dyn std::io::Write> = match &args.dst {
+        Some(dst) if dst.as_os_str() != "-" => Box::new(std::fs::File::create(dst)?),
+        _ => Box::new(std::io::stdout().lock()),
+    };
+    let mut output = std::io::BufWriter::new(output);
+    
+    write_code(args.lang, blend_file.header(), sdna, &mut output)?;
+    output.flush()?;
+    
+    log::info!("Generated {} struct/s.", sdna.structs.len());
     Ok(())
 }
 
-fn build_globber(excludes: Vec<String>) -> Option<globset::GlobSet> {
+fn build_globber(excludes: Vec<String>) -> Result<Option<globset::GlobSet>, UnblendError> {
     if excludes.is_empty() {
-        return None
+        return Ok(None)
     }
     
     use globset::{Glob, GlobSetBuilder};
     let mut builder = GlobSetBuilder::new();
     
     for glob in excludes {
-        let glob = Glob::new(&glob).expect("failed to build glob");
+        let glob = Glob::new(&glob).map_err(UnblendError::InvalidGlob)?;
         builder.add(glob);
     }
     
-    Some(builder.build().expect("failed to build globset"))
+    builder.build().map(Some).map_err(UnblendError::InvalidGlob)
 }
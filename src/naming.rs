@@ -0,0 +1,84 @@
+//! Naming of blocks after the datablocks they belong to, instead of their addresses.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::ids::IdBlock;
+use crate::index::BlockIndex;
+
+/// Assigns every block of an index a path without extension, in index order.
+///
+/// - ID blocks are named after their datablock, like `OB/Cube`.
+/// - `DATA` blocks belong to the ID block before them, like `ME/Suzanne/DATA/0`.
+/// - All other blocks are numbered per code, like `REND/0`.
+///
+/// Names are sanitized and made unique (ignoring case) by appending `~1`, `~2`, etc.
+pub fn name_blocks(index: &BlockIndex, ids: &[IdBlock]) -> Vec<String> {
+    let id_names: HashMap<usize, &str> = ids.iter().map(|id| (id.block, id.name.as_str())).collect();
+    
+    let mut used = HashSet::new();
+    let mut counters: HashMap<String, usize> = HashMap::new();
+    let mut owner: Option<String> = None;
+    
+    index.iter()
+        .enumerate()
+        .map(|(block, entry)| {
+            let code = sanitize(&entry.header.code.to_string());
+            let id_name = id_names.get(&block);
+            
+            let name = match id_name {
+                Some(id_name) => format!("{code}/{}", sanitize(id_name)),
+                None => {
+                    let dir = match &owner {
+                        Some(owner) if entry.header.code == b"DATA" => format!("{owner}/DATA"),
+                        _ => code,
+                    };
+                    
+                    let counter = counters.entry(dir.clone()).or_default();
+                    *counter += 1;
+                    format!("{dir}/{}", *counter - 1)
+                },
+            };
+            
+            let name = unique(&mut used, name);
+            
+            if id_name.is_some() {
+                owner = Some(name.clone());
+            } else if entry.header.code != b"DATA" {
+                owner = None;
+            }
+            
+            name
+        })
+        .collect()
+}
+
+/// Makes a name safe for use as a single path component of an archive entry.
+pub fn sanitize(name: &str) -> String {
+    let name: String = name.chars()
+        .map(|char| match char {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            char if char.is_control() => '_',
+            char => char,
+        })
+        .collect();
+    
+    let name = name.trim();
+    
+    match name {
+        "" | "." | ".." => String::from("_"),
+        name => name.to_string(),
+    }
+}
+
+/// Appends `~1`, `~2`, etc. to a name until it is unique, ignoring case.
+pub fn unique(used: &mut HashSet<String>, name: String) -> String {
+    let mut candidate = name.clone();
+    let mut suffix = 0;
+    
+    while !used.insert(candidate.to_lowercase()) {
+        suffix += 1;
+        candidate = format!("{name}~{suffix}");
+    }
+    
+    candidate
+}
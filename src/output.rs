@@ -1,4 +1,4 @@
-
+use crate::error::UnblendError;
 
 pub trait Output {
     
@@ -9,7 +9,7 @@ pub trait Output {
         data: &mut dyn std::io::Read
     ) -> std::io::Result<()>;
     
-    fn finish(&mut self);
+    fn finish(&mut self) -> std::io::Result<()>;
 }
 
 pub type OutputBox = Box<dyn Output>;
@@ -28,7 +28,7 @@ impl Output for OutputGlobber {
     ) -> std::io::Result<()> {
         
         if self.globset.is_match(path) {
-            eprintln!("Voiding file `{path}` of {size} byte/s.");
+            log::info!("Voiding file `{path}` of {size} byte/s.");
             // void the file
             return std::io::copy(
                 data,
@@ -39,9 +39,9 @@ impl Output for OutputGlobber {
         
         self.output.write_file(path, size, data)
     }
-
-    fn finish(&mut self) {
-        self.output.finish();
+    
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.output.finish()
     }
 }
 
@@ -53,7 +53,7 @@ impl Output for OutputToZip {
         size: u64,
         data: &mut dyn std::io::Read
     ) -> std::io::Result<()> {
-        eprintln!("Writing file `{path}` of {size} byte/s.");
+        log::info!("Writing file `{path}` of {size} byte/s.");
         
         let options = zip_next::write::FileOptions::default();
         
@@ -64,8 +64,9 @@ impl Output for OutputToZip {
         Ok(())
     }
     
-    fn finish(&mut self) {
-        self.0.finish().unwrap();
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.0.finish()?;
+        Ok(())
     }
 }
 
@@ -77,7 +78,7 @@ impl Output for OutputToTar {
         size: u64,
         data: &mut dyn std::io::Read
     ) -> std::io::Result<()> {
-        eprintln!("Writing file `{path}` of {size} byte/s.");
+        log::info!("Writing file `{path}` of {size} byte/s.");
         
         let mut header = tar::Header::new_gnu();
         header.set_size(size);
@@ -85,45 +86,49 @@ impl Output for OutputToTar {
         Ok(())
     }
     
-    fn finish(&mut self) {
-        self.0.finish().unwrap();
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.0.finish()
     }
 }
 
 /// Detect what type of file we should write...
-pub fn select_output(dst: &std::path::PathBuf) -> OutputBox {
+pub fn select_output(dst: &std::path::PathBuf) -> Result<OutputBox, UnblendError> {
     if dst == std::path::Path::new("-") {
-        eprintln!("Writing output to STDOUT as TAR");
-        return Box::new(OutputToTar (
+        log::info!("Writing output to STDOUT as TAR");
+        return Ok(Box::new(OutputToTar (
             tar::Builder::new(
                 Box::new(
                     std::io::stdout().lock()
                 )
             )
-        ))
+        )))
     }
     
-    match &*dst.extension().expect("Unable to determine output format: OUT path has no file-extension").to_string_lossy() {
+    let Some(extension) = dst.extension() else {
+        return Err(UnblendError::UnknownOutputFormat(dst.clone()))
+    };
+    
+    Ok(match &*extension.to_string_lossy() {
         "zip" => {
-            eprintln!("Writing output to {dst:?} as ZIP");
+            log::info!("Writing output to {dst:?} as ZIP");
             Box::new(
                 OutputToZip (
                     zip_next::ZipWriter::new(
-                        std::fs::File::create(dst).expect("Failed to open output for writing")
+                        std::fs::File::create(dst)?
                     )
                 )
             )
         },
         "tar" => {
-            eprintln!("Writing output to {dst:?} as TAR");
+            log::info!("Writing output to {dst:?} as TAR");
             Box::new(OutputToTar (
                 tar::Builder::new(
                     Box::new(
-                        std::fs::File::create(dst).expect("Failed to open output for writing")
+                        std::fs::File::create(dst)?
                     )
                 )
             ))
         },
-        _ => panic!("Unable to determine output format from {dst:?}")
-    }
+        _ => return Err(UnblendError::UnknownOutputFormat(dst.clone()))
+    })
 }
@@ -0,0 +1,113 @@
+//! Resolution of the old memory addresses stored in pointers to the blocks they point into.
+
+use crate::decode::{Decoder, Value};
+use crate::index::*;
+
+/// Maps the address range of every block, `addr .. addr + size`, to the block.
+#[derive(Debug, Clone, Default)]
+pub struct AddressMap {
+    /// Start, end and index of every block with an address, sorted by start and index.
+    ranges: Vec<(u64, u64, usize)>,
+    /// Size of one element of every block, by index.
+    element_sizes: Vec<u64>,
+}
+
+/// What a pointer points to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Null,
+    Block(PointerTarget),
+    /// The address is not within any block; Blender does not write runtime-only data.
+    Dangling,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PointerTarget {
+    /// Index of the block within the [`BlockIndex`].
+    pub block: usize,
+    /// Offset of the pointed-to byte within the payload.
+    pub offset: u64,
+    /// Index of the pointed-to element, for blocks with a `count` above one.
+    pub element: u64,
+}
+
+impl AddressMap {
+    pub fn new(index: &BlockIndex) -> Self {
+        let mut ranges: Vec<_> = index.iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.header.addr != 0)
+            .map(|(block, entry)| (entry.header.addr, entry.header.addr.saturating_add(entry.header.size), block))
+            .collect();
+        
+        ranges.sort_unstable_by_key(|&(start, _, block)| (start, block));
+        
+        let element_sizes = index.iter()
+            .map(|entry| match entry.header.count {
+                0 => entry.header.size,
+                count => entry.header.size / count,
+            })
+            .collect();
+        
+        Self { ranges, element_sizes }
+    }
+    
+    /// Finds the block an address points into.
+    /// 
+    /// Should several blocks share the address, the first one in the file wins.
+    pub fn resolve(&self, addr: u64) -> Resolution {
+        if addr == 0 {
+            return Resolution::Null
+        }
+        
+        let after = self.ranges.partition_point(|&(start, _, _)| start <= addr);
+        let Some(&(start, _, _)) = after.checked_sub(1).map(|last| &self.ranges[last]) else {
+            return Resolution::Dangling
+        };
+        
+        let first = self.ranges[..after].partition_point(|&(other, _, _)| other < start);
+        let (start, end, block) = self.ranges[first];
+        
+        // Empty blocks can still be pointed at.
+        if addr >= end && addr != start {
+            return Resolution::Dangling
+        }
+        
+        let offset = addr - start;
+        let element = match self.element_sizes[block] {
+            0 => 0,
+            size => offset / size,
+        };
+        
+        Resolution::Block(PointerTarget { block, offset, element })
+    }
+    
+    /// Whether a raw payload is an array of pointers.
+    /// 
+    /// Blender writes such arrays as raw blocks, without a struct;
+    /// they are recognized by every element pointing at the start of a block.
+    pub fn is_pointer_array(&self, decoder: &Decoder, data: &[u8]) -> bool {
+        let size = decoder.pointer_size();
+        if data.is_empty() || !data.len().is_multiple_of(size) {
+            return false
+        }
+        
+        let addrs: Vec<u64> = (0..data.len())
+            .step_by(size)
+            .filter_map(|offset| decoder.read_pointer(data, offset))
+            .collect();
+        
+        addrs.iter().any(|&addr| addr != 0) && addrs.iter().all(|&addr| match self.resolve(addr) {
+            Resolution::Null => true,
+            Resolution::Block(target) => target.offset == 0,
+            Resolution::Dangling => false,
+        })
+    }
+    
+    /// Resolves every pointer within a decoded value, by the path of its field.
+    pub fn resolve_value(&self, value: &Value) -> Vec<(String, u64, Resolution)> {
+        value.pointers()
+            .into_iter()
+            .map(|(path, addr)| (path, addr, self.resolve(addr)))
+            .collect()
+    }
+}
@@ -20,20 +20,20 @@ pub trait ReadExt: Read {
     }
     
     fn read_cstr(&mut self) -> Result<std::ffi::CString> {
-        let mut buf = [0u8; 256];
-        let mut len = 0;
+        let mut buf = Vec::new();
         
         loop {
             let byte = self.read_byte()?;
-            buf[len] = byte;
-            len += 1;
             
             if byte == 0 {
                 break;
             }
+            
+            buf.push(byte);
         }
         
-        Ok(std::ffi::CStr::from_bytes_with_nul(&buf[..len]).unwrap().to_owned())
+        // The loop stops at the first nul, so there can't be an interior one.
+        Ok(std::ffi::CString::new(buf).unwrap())
     }
     
     fn take_borrowed<'b, 'r: 'b>(&'r mut self, len: usize) -> BorrowedTake<'b, Self> {
@@ -42,7 +42,7 @@ pub trait ReadExt: Read {
             rem: len
         }
     }
-    
+
 }
 
 impl<R> ReadExt for R where R: Read {}
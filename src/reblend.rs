@@ -0,0 +1,289 @@
+//! Reassembly of a blend-file from an archive, as written by exploding one.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Write};
+use std::path::Path;
+
+use crate::blend::*;
+use crate::error::UnblendError;
+
+/// Writes a blend-file from the entries of an exploded archive, given in archive order.
+///
+/// - `blend.txt` becomes the file-header.
+/// - Every `<NAME>.txt` with block metadata becomes a block, with `<NAME>.bin` as payload.
+/// - Blocks are written in the order of their `seq`, or else in the order of their `.txt`;
+///   blocks that come early are held back until the ones before them arrived.
+/// - All other entries, like `.json` files or the `DNA1/` listings, are ignored.
+///
+/// The size of a block is taken from its `.bin`, so that payloads can be edited.
+pub struct Reblend<W: Write> {
+    write: W,
+    header: Option<BlendHeader>,
+    /// Payloads still waiting for their metadata, and vice versa, by entry path without extension.
+    pending_payloads: HashMap<String, Vec<u8>>,
+    pending_metas: HashMap<String, (Option<usize>, BlendChunkHeader)>,
+    /// Blocks that came before the ones preceding them, by `seq`.
+    waiting: BTreeMap<usize, (String, BlendChunkHeader, Vec<u8>)>,
+    /// The `seq` of the next block to be written.
+    next_seq: usize,
+    blocks: usize,
+    ended: bool,
+}
+
+impl<W: Write> Reblend<W> {
+    pub fn new(write: W) -> Self {
+        Self {
+            write,
+            header: None,
+            pending_payloads: HashMap::new(),
+            pending_metas: HashMap::new(),
+            waiting: BTreeMap::new(),
+            next_seq: 0,
+            blocks: 0,
+            ended: false,
+        }
+    }
+    
+    pub fn add_entry(&mut self, path: &str, data: &mut dyn Read) -> Result<(), UnblendError> {
+        if path == "blend.txt" {
+            let header = parse_header(&read_text(path, data)?)
+                .ok_or_else(|| UnblendError::malformed_archive(path, "not a blend-file header"))?;
+            
+            if self.header.is_some() {
+                return Err(UnblendError::malformed_archive(path, "the archive has more than one"))
+            }
+            
+            write_header(&header, &mut self.write)?;
+            self.header = Some(header);
+            return self.flush()
+        }
+        
+        if let Some(stem) = path.strip_suffix(".bin") {
+            let mut payload = Vec::new();
+            data.read_to_end(&mut payload)?;
+            
+            return match self.pending_metas.remove(stem) {
+                Some((seq, chunk)) => self.add_block(stem, seq, chunk, payload),
+                None => {
+                    self.pending_payloads.insert(stem.to_string(), payload);
+                    Ok(())
+                },
+            }
+        }
+        
+        let Some(stem) = path.strip_suffix(".txt") else {
+            return Ok(())
+        };
+        
+        let Some((seq, chunk)) = parse_meta(&read_text(path, data)?) else {
+            return Ok(())
+        };
+        
+        match self.pending_payloads.remove(stem) {
+            Some(payload) => self.add_block(stem, seq, chunk, payload),
+            None => {
+                self.pending_metas.insert(stem.to_string(), (seq, chunk));
+                Ok(())
+            },
+        }
+    }
+    
+    fn add_block(&mut self, stem: &str, seq: Option<usize>, chunk: BlendChunkHeader, payload: Vec<u8>) -> Result<(), UnblendError> {
+        let Some(seq) = seq else {
+            if self.header.is_none() {
+                return Err(UnblendError::malformed_archive(format!("{stem}.txt"), "comes before `blend.txt`"))
+            }
+            
+            return self.write_block(stem, chunk, payload)
+        };
+        
+        if seq < self.next_seq || self.waiting.contains_key(&seq) {
+            return Err(UnblendError::malformed_archive(format!("{stem}.txt"), format!("block #{seq} came twice")))
+        }
+        
+        self.waiting.insert(seq, (stem.to_string(), chunk, payload));
+        self.flush()
+    }
+    
+    /// Writes the blocks that are next in line, once the file-header has been written.
+    fn flush(&mut self) -> Result<(), UnblendError> {
+        if self.header.is_none() {
+            return Ok(())
+        }
+        
+        while let Some((stem, chunk, payload)) = self.waiting.remove(&self.next_seq) {
+            self.write_block(&stem, chunk, payload)?;
+            self.next_seq += 1;
+        }
+        
+        Ok(())
+    }
+    
+    fn write_block(&mut self, stem: &str, mut chunk: BlendChunkHeader, payload: Vec<u8>) -> Result<(), UnblendError> {
+        if self.ended {
+            return Err(UnblendError::malformed_archive(format!("{stem}.txt"), "comes after the `ENDB` block"))
+        }
+        
+        if payload.len() as u64 != chunk.size {
+            log::info!("Block `{stem}` changed in size from {} to {} byte/s.", chunk.size, payload.len());
+            chunk.size = payload.len() as u64;
+        }
+        
+        write_chunk_header(self.header.as_ref().unwrap(), &chunk, &mut self.write)?;
+        self.write.write_all(&payload)?;
+        
+        self.blocks += 1;
+        self.ended = chunk.code == b"ENDB";
+        Ok(())
+    }
+    
+    /// Checks that the file is complete, returning the number of blocks written.
+    pub fn finish(mut self) -> Result<usize, UnblendError> {
+        if self.header.is_none() {
+            return Err(UnblendError::malformed_archive("blend.txt", "is missing"))
+        }
+        
+        // Blocks may have been excluded from the archive.
+        if let Some((&last, _)) = self.waiting.last_key_value() {
+            let missing = last + 1 - self.next_seq - self.waiting.len();
+            log::warn!("{missing} block/s are missing from the archive.");
+        }
+        
+        for (_, (stem, chunk, payload)) in std::mem::take(&mut self.waiting) {
+            self.write_block(&stem, chunk, payload)?;
+        }
+        
+        if !self.ended {
+            return Err(UnblendError::malformed_archive("ENDB", "is missing; the archive is incomplete"))
+        }
+        
+        if !self.pending_payloads.is_empty() {
+            log::warn!("{} `.bin` file/s without block metadata were left out.", self.pending_payloads.len());
+        }
+        
+        if !self.pending_metas.is_empty() {
+            log::warn!("{} block/s without `.bin` file were left out.", self.pending_metas.len());
+        }
+        
+        self.write.flush()?;
+        Ok(self.blocks)
+    }
+}
+
+/// Calls `visit` with every entry of a ZIP or TAR archive, in archive order.
+///
+/// By specifying `-` as `src`, a TAR is read from STDIN.
+pub fn for_each_entry(src: &Path, visit: &mut dyn FnMut(&str, &mut dyn Read) -> Result<(), UnblendError>) -> Result<(), UnblendError> {
+    if src == Path::new("-") {
+        log::info!("Reading archive from STDIN as TAR");
+        return for_each_tar_entry(std::io::stdin().lock(), visit)
+    }
+    
+    match src.extension().map(|ext| ext.to_string_lossy()).as_deref() {
+        Some("zip") => {
+            log::info!("Reading archive from {src:?} as ZIP");
+            let mut archive = zip_next::ZipArchive::new(std::fs::File::open(src)?)
+                .map_err(|err| UnblendError::malformed_archive(src.to_string_lossy(), err.to_string()))?;
+            
+            for index in 0..archive.len() {
+                let mut file = archive.by_index(index)
+                    .map_err(|err| UnblendError::malformed_archive(src.to_string_lossy(), err.to_string()))?;
+                
+                if file.is_file() {
+                    let name = file.name().to_string();
+                    visit(&name, &mut file)?;
+                }
+            }
+            
+            Ok(())
+        },
+        Some("tar") => {
+            log::info!("Reading archive from {src:?} as TAR");
+            for_each_tar_entry(std::io::BufReader::new(std::fs::File::open(src)?), visit)
+        },
+        _ => Err(UnblendError::UnknownArchiveFormat(src.to_path_buf())),
+    }
+}
+
+fn for_each_tar_entry(read: impl Read, visit: &mut dyn FnMut(&str, &mut dyn Read) -> Result<(), UnblendError>) -> Result<(), UnblendError> {
+    let mut archive = tar::Archive::new(read);
+    
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        
+        if entry.header().entry_type().is_file() {
+            let name = entry.path()?.to_string_lossy().replace('\\', "/");
+            visit(&name, &mut entry)?;
+        }
+    }
+    
+    Ok(())
+}
+
+fn read_text(path: &str, data: &mut dyn Read) -> Result<String, UnblendError> {
+    let mut text = String::new();
+    data.read_to_string(&mut text)
+        .map_err(|_| UnblendError::malformed_archive(path, "not valid UTF-8"))?;
+    Ok(text)
+}
+
+/// The tab-separated `key\tvalue`-lines of a `.txt` entry.
+fn parse_lines(text: &str) -> HashMap<&str, &str> {
+    text.lines().filter_map(|line| line.split_once('\t')).collect()
+}
+
+fn parse_hex(value: &str) -> Option<u64> {
+    u64::from_str_radix(value.strip_prefix("0x")?, 16).ok()
+}
+
+/// Parses `blend.txt`, the [`BlendHeader`] as displayed.
+fn parse_header(text: &str) -> Option<BlendHeader> {
+    let lines = parse_lines(text);
+    
+    let layout = match *lines.get("layout")? {
+        "Legacy" => BlendHeaderLayout::Legacy,
+        layout => BlendHeaderLayout::Large { format_version: layout.strip_prefix("Large-")?.parse().ok()? },
+    };
+    
+    let usize = match *lines.get("usize")? {
+        "U32" => BlendUsize::U32,
+        "U64" => BlendUsize::U64,
+        _ => return None,
+    };
+    
+    let endian = match *lines.get("endian")? {
+        "LE" => BlendEndian::LE,
+        "BE" => BlendEndian::BE,
+        _ => return None,
+    };
+    
+    let (major, minor) = lines.get("version")?.split_once('.')?;
+    let version = BlendVersion {
+        major: major.parse().ok()?,
+        minor: minor.parse().ok()?,
+    };
+    
+    Some(BlendHeader { layout, usize, endian, version })
+}
+
+/// Parses the metadata of a block, as written next to its payload, along with its `seq`, if any.
+fn parse_meta(text: &str) -> Option<(Option<usize>, BlendChunkHeader)> {
+    let lines = parse_lines(text);
+    
+    let name = lines.get("code")?.as_bytes();
+    let mut code = [0u8; 4];
+    code.get_mut(..name.len())?.copy_from_slice(name);
+    
+    let seq = match lines.get("seq") {
+        Some(seq) => Some(seq.parse().ok()?),
+        None => None,
+    };
+    
+    Some((seq, BlendChunkHeader {
+        code: BlendChunkCode(code),
+        size: parse_hex(lines.get("size")?)?,
+        addr: parse_hex(lines.get("addr")?)?,
+        sdna: parse_hex(lines.get("sdna")?)? as u32,
+        count: lines.get("count")?.parse().ok()?,
+    }))
+}
@@ -0,0 +1,272 @@
+//! Renumbering of block addresses, so that they no longer depend on where Blender happened to allocate the data.
+
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::io::{Read, Seek};
+
+use crate::blend::*;
+use crate::blend_file::BlendFile;
+use crate::decode::Decoder;
+use crate::error::UnblendError;
+use crate::ids::list_ids;
+use crate::naming::unique;
+use crate::pointers::*;
+use crate::sdna::Sdna;
+
+/// A deterministic replacement for the address of every block.
+///
+/// Every block gets a stable key: datablocks by their code and ID name, like `OB/Cube`,
+/// the blocks they point to by the path of the pointer, like `ME/Suzanne.mvert`,
+/// and all other blocks by their code and position, like `REND#0`.
+/// The new address is derived from a hash of the key,
+/// so that unrelated changes to the file leave it untouched.
+#[derive(Debug, Clone)]
+pub struct Renumbering {
+    blend: BlendHeader,
+    /// The new address of every block, by index.
+    addrs: Vec<u64>,
+    /// The key of every block, by index.
+    keys: Vec<String>,
+    /// The offset of every pointer within every block, by index.
+    pointers: Vec<Vec<usize>>,
+    address_map: AddressMap,
+}
+
+impl Renumbering {
+    pub fn new<R: Read + Seek>(blend_file: &mut BlendFile<R>) -> Result<Self, UnblendError> {
+        let blend = *blend_file.header();
+        let index = blend_file.index().cloned().unwrap_or_default();
+        let sdna = match blend_file.sdna().cloned() {
+            Some(sdna) => sdna,
+            None => Sdna::new(Vec::new(), Vec::new(), Vec::new(), Vec::new(), blend.usize.len() as usize)?,
+        };
+        let decoder = Decoder::new(&blend, &sdna);
+        let address_map = AddressMap::new(&index);
+        
+        // The blocks every block points to, by the path of the pointer.
+        let mut pointers = Vec::with_capacity(index.len());
+        let mut targets = Vec::with_capacity(index.len());
+        
+        for entry in index.iter() {
+            let data = blend_file.read_block(entry)?;
+            let fields = pointer_fields(&decoder, &address_map, &entry.header, &data);
+            
+            targets.push(fields.iter()
+                .filter_map(|(path, offset)| match address_map.resolve(decoder.read_pointer(&data, *offset)?) {
+                    Resolution::Block(target) => Some((path.clone(), target.block)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>());
+            
+            pointers.push(fields.into_iter().map(|(_, offset)| offset).collect());
+        }
+        
+        let mut keys = Keys {
+            keys: vec![None; index.len()],
+            order: Vec::with_capacity(index.len()),
+            used: HashSet::new(),
+        };
+        
+        // Datablocks first, so that pointers between them don't matter.
+        let mut roots: Vec<(String, usize)> = list_ids(blend_file)?
+            .into_iter()
+            .map(|id| {
+                let key = format!("{}/{}", id.code, id.name);
+                match (&id.library, id.is_linked()) {
+                    (Some(library), _) => (format!("{key}@{library}"), id.block),
+                    (None, true) => (format!("{key}@?"), id.block),
+                    (None, false) => (key, id.block),
+                }
+            })
+            .collect();
+        
+        roots.sort();
+        
+        for (key, block) in &roots {
+            keys.assign(*block, key.clone());
+        }
+        
+        for (_, block) in &roots {
+            keys.visit(*block, &targets);
+        }
+        
+        // Whatever is not reachable from any datablock is numbered per code, in file order.
+        let mut counters = std::collections::HashMap::new();
+        
+        for (block, entry) in index.iter().enumerate() {
+            if keys.keys[block].is_some() {
+                continue;
+            }
+            
+            let counter = counters.entry(entry.header.code.to_string()).or_insert(0usize);
+            keys.assign(block, format!("{}#{counter}", entry.header.code));
+            *counter += 1;
+            keys.visit(block, &targets);
+        }
+        
+        let chunks: Vec<_> = index.iter().map(|entry| entry.header).collect();
+        let addrs = allocate(&blend, &chunks, &keys.keys, &keys.order);
+        let keys = keys.keys.into_iter().map(Option::unwrap_or_default).collect();
+        
+        Ok(Self {
+            blend,
+            addrs,
+            keys,
+            pointers,
+            address_map,
+        })
+    }
+    
+    /// The new address of a block, by its index.
+    /// 
+    /// Fails for blocks past the index, as when reading another file than the one indexed;
+    /// the error names the block by the `offset` of its header and its `code`.
+    pub fn block_addr(&self, block: usize, offset: u64, code: BlendChunkCode) -> Result<u64, UnblendError> {
+        self.addrs.get(block).copied().ok_or_else(|| truncated(offset, code))
+    }
+    
+    /// The stable key of a block, by its index.
+    pub fn block_key(&self, block: usize) -> &str {
+        &self.keys[block]
+    }
+    
+    /// Maps an old address to the new one.
+    ///
+    /// Dangling pointers become null, just like Blender treats them when loading the file.
+    pub fn map(&self, addr: u64) -> u64 {
+        match self.address_map.resolve(addr) {
+            Resolution::Block(target) => self.addrs[target.block] + target.offset,
+            _ => 0,
+        }
+    }
+    
+    /// Rewrites every pointer within the payload of a block, by its index.
+    /// 
+    /// Fails for blocks past the index, just like [`Self::block_addr`].
+    pub fn rewrite_block(&self, block: usize, offset: u64, code: BlendChunkCode, data: &mut [u8]) -> Result<(), UnblendError> {
+        let endian = self.blend.endian;
+        let pointers = self.pointers.get(block).ok_or_else(|| truncated(offset, code))?;
+        
+        for &offset in pointers {
+            match self.blend.usize {
+                BlendUsize::U32 => if let Some(bytes) = crate::byte_ext::copy_at::<4>(data, offset) {
+                    let addr = self.map(endian.u32(bytes).into()) as u32;
+                    data[offset..offset + 4].copy_from_slice(&endian.u32_bytes(addr));
+                },
+                BlendUsize::U64 => if let Some(bytes) = crate::byte_ext::copy_at::<8>(data, offset) {
+                    let addr = self.map(endian.u64(bytes));
+                    data[offset..offset + 8].copy_from_slice(&endian.u64_bytes(addr));
+                },
+            }
+        }
+        
+        Ok(())
+    }
+}
+
+fn truncated(offset: u64, code: BlendChunkCode) -> UnblendError {
+    UnblendError::TruncatedBlock {
+        offset,
+        code: code.to_string(),
+    }
+}
+
+/// The keys given to blocks so far, and in which order.
+struct Keys {
+    keys: Vec<Option<String>>,
+    order: Vec<usize>,
+    used: HashSet<String>,
+}
+
+impl Keys {
+    fn assign(&mut self, block: usize, key: String) {
+        self.keys[block] = Some(unique(&mut self.used, key));
+        self.order.push(block);
+    }
+    
+    /// Gives every block reachable from `root` a key, breadth-first, after the path of the first pointer to it.
+    fn visit(&mut self, root: usize, targets: &[Vec<(String, usize)>]) {
+        let mut queue = VecDeque::from([root]);
+        
+        while let Some(block) = queue.pop_front() {
+            for (path, target) in &targets[block] {
+                if self.keys[*target].is_none() {
+                    let key = format!("{}.{path}", self.keys[block].as_deref().unwrap_or_default());
+                    self.assign(*target, key);
+                    queue.push_back(*target);
+                }
+            }
+        }
+    }
+}
+
+/// The path and offset of every pointer within a block payload.
+fn pointer_fields(decoder: &Decoder, address_map: &AddressMap, chunk: &BlendChunkHeader, data: &[u8]) -> Vec<(String, usize)> {
+    if chunk.code == b"DNA1" || chunk.code == b"ENDB" {
+        return Vec::new()
+    }
+    
+    if let Some(strct) = decoder.block_struct(chunk) {
+        let fields = decoder.pointer_fields(strct);
+        
+        return (0..chunk.count as usize)
+            .flat_map(|element| fields.iter().map(move |(path, offset)| match chunk.count {
+                1 => (path.clone(), *offset),
+                _ => (format!("[{element}].{path}"), element * strct.size as usize + offset),
+            }))
+            .collect()
+    }
+    
+    let size = decoder.pointer_size();
+    
+    match address_map.is_pointer_array(decoder, data) {
+        true => (0..data.len()).step_by(size).map(|offset| (format!("[{}]", offset / size), offset)).collect(),
+        false => Vec::new(),
+    }
+}
+
+/// Gives every block with an address a new one, derived from its key.
+///
+/// Blocks are placed in the given order; should the address be taken, the next free one is used.
+fn allocate(blend: &BlendHeader, chunks: &[BlendChunkHeader], keys: &[Option<String>], order: &[usize]) -> Vec<u64> {
+    // Every block gets its own range of addresses, large enough for all but the biggest blocks.
+    let (granularity, slots): (u64, u64) = match blend.usize {
+        BlendUsize::U32 => (1 << 12, 1 << 20),
+        BlendUsize::U64 => (1 << 32, 1 << 31),
+    };
+    
+    let mut addrs: Vec<u64> = chunks.iter().map(|chunk| chunk.addr).collect();
+    let mut taken: BTreeMap<u64, u64> = BTreeMap::new();
+    
+    for &block in order {
+        if chunks[block].addr == 0 {
+            continue;
+        }
+        
+        let len = chunks[block].size.max(1);
+        let key = keys[block].as_deref().unwrap_or_default();
+        let mut slot = fnv1a(key.as_bytes()) % (slots - 1) + 1;
+        
+        for _ in 1..slots {
+            let start = slot * granularity;
+            let end = start.saturating_add(len);
+            
+            let fits = end <= slots * granularity;
+            let free = taken.range(..end).next_back().is_none_or(|(_, &taken_end)| taken_end <= start);
+            
+            if fits && free {
+                taken.insert(start, end);
+                addrs[block] = start;
+                break;
+            }
+            
+            slot = slot % (slots - 1) + 1;
+        }
+    }
+    
+    addrs
+}
+
+/// The 64-bit FNV-1a hash, which unlike the hasher of the standard library is stable.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+
+use crate::error::UnblendError;
+
+/// The struct-definitions of a blend-file, as parsed from its `DNA1` block.
+#[derive(Debug, Clone)]
+pub struct Sdna {
+    /// The `NAME`-list: every field-name used by any struct.
+    pub names: Vec<String>,
+    /// The `TYPE`- and `TLEN`-lists: every type, with its size in bytes.
+    pub types: Vec<SdnaType>,
+    /// The `STRC`-list, indexed by SDNA index.
+    pub structs: Vec<SdnaStruct>,
+    /// The SDNA index of every type that is a struct, by type index.
+    structs_by_type: HashMap<u16, usize>,
+    structs_by_name: HashMap<String, usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SdnaType {
+    pub name: String,
+    pub size: u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct SdnaStruct {
+    /// Index of this struct within the `STRC`-list, as referenced by block-headers.
+    pub sdna: usize,
+    /// Index of this structs type within the `TYPE`-list.
+    pub type_index: u16,
+    pub name: String,
+    pub size: u16,
+    pub fields: Vec<SdnaField>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SdnaField {
+    pub type_index: u16,
+    pub name_index: u16,
+    /// The name of the fields type, like `float`.
+    pub type_name: String,
+    /// The name of the field as written in the DNA, like `co[3]`.
+    pub name: String,
+    pub parsed: FieldName,
+    /// Offset of the field within its struct, in bytes.
+    pub offset: usize,
+    /// Size of the field, including all array elements, in bytes.
+    pub size: usize,
+}
+
+/// A field-name of the DNA, like `*next`, `name[64]` or `(*func)()`, taken apart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldName {
+    /// The bare identifier, like `next`, `name` or `func`.
+    pub ident: String,
+    /// How many `*` the name has; `**mat` has two.
+    pub pointer_depth: u8,
+    /// The array dimensions, outermost first; `co[3][2]` has `[3, 2]`.
+    pub array_dims: Vec<usize>,
+    pub is_function_pointer: bool,
+}
+
+impl FieldName {
+    /// Takes a field-name apart; `None` if an array dimension is not a number.
+    pub fn parse(name: &str) -> Option<Self> {
+        let (inner, is_function_pointer) = match name.strip_prefix('(') {
+            Some(rest) => (rest.split(')').next().unwrap_or(rest), true),
+            None => (name, false),
+        };
+        
+        let ident = inner.trim_start_matches('*');
+        let pointer_depth = (inner.len() - ident.len()) as u8;
+        
+        let (ident, dims) = match ident.find('[') {
+            Some(start) => ident.split_at(start),
+            None => (ident, ""),
+        };
+        
+        let array_dims = dims.split('[')
+            .filter_map(|dim| dim.strip_suffix(']'))
+            .map(|dim| dim.trim().parse().ok())
+            .collect::<Option<_>>()?;
+        
+        Some(Self {
+            ident: ident.to_string(),
+            pointer_depth,
+            array_dims,
+            is_function_pointer,
+        })
+    }
+    
+    /// Whether the field holds a pointer (or an array of them) instead of a value.
+    pub fn is_pointer(&self) -> bool {
+        self.pointer_depth > 0 || self.is_function_pointer
+    }
+    
+    /// The total number of array elements; `1` if the field is not an array.
+    /// 
+    /// Can't overflow for the fields of an [`Sdna`], as those are checked when it is built.
+    pub fn array_len(&self) -> usize {
+        self.array_dims.iter().product()
+    }
+    
+    /// The total number of array elements, unless that overflows.
+    pub fn checked_array_len(&self) -> Option<usize> {
+        self.array_dims.iter().try_fold(1usize, |len, &dim| len.checked_mul(dim))
+    }
+    
+    /// Spells out the full type of a field with this name, like `char[64]`, `ID*` or `void (*)()`.
+    pub fn type_string(&self, type_name: &str) -> String {
+        use std::fmt::Write;
+        let mut string = String::from(type_name);
+        
+        if self.is_function_pointer {
+            string.push_str(" (");
+        }
+        
+        for _ in 0..self.pointer_depth {
+            string.push('*');
+        }
+        
+        for dim in &self.array_dims {
+            write!(&mut string, "[{dim}]").unwrap();
+        }
+        
+        if self.is_function_pointer {
+            string.push_str(")()");
+        }
+        
+        string
+    }
+}
+
+impl Sdna {
+    /// Builds the model from the raw lists; the indices must have been validated.
+    ///
+    /// The `pointer_size` is needed to lay out the fields of each struct.
+    /// Fails if a field-name can't be parsed, or a field is too large to be laid out.
+    pub fn new(names: Vec<String>, types: Vec<String>, lengths: Vec<u16>, structs: Vec<(u16, Vec<(u16, u16)>)>, pointer_size: usize) -> Result<Self, UnblendError> {
+        Self::with_offsets(names, types, lengths, structs, &[], pointer_size)
+    }
+    
+    /// Like [`Sdna::new`], given where the field-list of each struct starts within the `DNA1` block,
+    /// so that errors point at the offending field; errors of other structs are reported at offset 0.
+    pub(crate) fn with_offsets(names: Vec<String>, types: Vec<String>, lengths: Vec<u16>, structs: Vec<(u16, Vec<(u16, u16)>)>, offsets: &[usize], pointer_size: usize) -> Result<Self, UnblendError> {
+        let types: Vec<SdnaType> = types.into_iter()
+            .zip(lengths)
+            .map(|(name, size)| SdnaType { name, size })
+            .collect();
+        
+        let structs: Vec<SdnaStruct> = structs.into_iter()
+            .enumerate()
+            .map(|(sdna, (type_index, fields))| {
+                let strct = &types[type_index as usize];
+                let mut offset = 0usize;
+                
+                let fields = fields.into_iter()
+                    .enumerate()
+                    .map(|(field, (type_index, name_index))| {
+                        let name = names[name_index as usize].clone();
+                        // Each field is a pair of shorts: the index of its type and of its name.
+                        let at = offsets.get(sdna).map_or(0, |offset| offset + field * 4);
+                        let malformed = |reason: &str| UnblendError::malformed_dna(at, format!("field `{name}` of struct `{}` {reason}", strct.name));
+                        
+                        let parsed = FieldName::parse(&name)
+                            .ok_or_else(|| malformed("has an invalid array dimension"))?;
+                        
+                        let element_size = match parsed.is_pointer() {
+                            true => pointer_size,
+                            false => types[type_index as usize].size as usize,
+                        };
+                        
+                        let size = parsed.checked_array_len()
+                            .and_then(|len| element_size.checked_mul(len))
+                            .ok_or_else(|| malformed("is too large"))?;
+                        
+                        let field_offset = offset;
+                        offset = offset.checked_add(size)
+                            .ok_or_else(|| malformed("is too large"))?;
+                        
+                        Ok(SdnaField {
+                            type_index,
+                            name_index,
+                            type_name: types[type_index as usize].name.clone(),
+                            name,
+                            parsed,
+                            offset: field_offset,
+                            size,
+                        })
+                    })
+                    .collect::<Result<_, UnblendError>>()?;
+                
+                Ok(SdnaStruct {
+                    sdna,
+                    type_index,
+                    name: strct.name.clone(),
+                    size: strct.size,
+                    fields,
+                })
+            })
+            .collect::<Result<_, UnblendError>>()?;
+        
+        // The first definition wins, should a struct be defined twice.
+        let mut structs_by_type = HashMap::with_capacity(structs.len());
+        let mut structs_by_name = HashMap::with_capacity(structs.len());
+        for strct in structs.iter().rev() {
+            structs_by_type.insert(strct.type_index, strct.sdna);
+            structs_by_name.insert(strct.name.clone(), strct.sdna);
+        }
+        
+        Ok(Self {
+            names,
+            types,
+            structs,
+            structs_by_type,
+            structs_by_name,
+        })
+    }
+    
+    /// Looks up a struct by its SDNA index, i.e. the `sdna` of a [`crate::BlendChunkHeader`].
+    pub fn struct_by_sdna(&self, sdna: u32) -> Option<&SdnaStruct> {
+        self.structs.get(sdna as usize)
+    }
+    
+    /// Looks up the struct of the given type index, if that type is a struct.
+    pub fn struct_by_type(&self, type_index: u16) -> Option<&SdnaStruct> {
+        self.structs_by_type.get(&type_index).map(|&sdna| &self.structs[sdna])
+    }
+    
+    pub fn struct_by_name(&self, name: &str) -> Option<&SdnaStruct> {
+        self.structs_by_name.get(name).map(|&sdna| &self.structs[sdna])
+    }
+    
+    pub fn type_by_index(&self, type_index: u16) -> Option<&SdnaType> {
+        self.types.get(type_index as usize)
+    }
+}
+
+impl SdnaStruct {
+    /// Looks up a field by its bare identifier, like `next` for `*next`.
+    pub fn field_by_name(&self, ident: &str) -> Option<&SdnaField> {
+        self.fields.iter().find(|field| field.parsed.ident == ident)
+    }
+    
+    /// The size of all fields together, which should match the size of the struct.
+    pub fn fields_size(&self) -> usize {
+        self.fields.last().map(|field| field.offset + field.size).unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn field(name: &str) -> FieldName {
+        FieldName::parse(name).unwrap()
+    }
+    
+    #[test]
+    fn parse_field_names() {
+        assert_eq!(field("flag"), FieldName { ident: "flag".into(), pointer_depth: 0, array_dims: vec![], is_function_pointer: false });
+        assert_eq!(field("**mat"), FieldName { ident: "mat".into(), pointer_depth: 2, array_dims: vec![], is_function_pointer: false });
+        assert_eq!(field("co[3][2]"), FieldName { ident: "co".into(), pointer_depth: 0, array_dims: vec![3, 2], is_function_pointer: false });
+        assert_eq!(field("*mtex[18]"), FieldName { ident: "mtex".into(), pointer_depth: 1, array_dims: vec![18], is_function_pointer: false });
+        assert_eq!(field("(*func)()"), FieldName { ident: "func".into(), pointer_depth: 1, array_dims: vec![], is_function_pointer: true });
+    }
+    
+    #[test]
+    fn reject_invalid_array_dimensions() {
+        assert_eq!(FieldName::parse("name[]"), None);
+        assert_eq!(FieldName::parse("name[x]"), None);
+        assert_eq!(FieldName::parse("name[-1]"), None);
+        assert_eq!(FieldName::parse("a[4294967296][4294967296]").and_then(|name| name.checked_array_len()), None);
+    }
+    
+    #[test]
+    fn type_strings() {
+        assert_eq!(field("name[64]").type_string("char"), "char[64]");
+        assert_eq!(field("*next").type_string("ID"), "ID*");
+        assert_eq!(field("(*func)()").type_string("void"), "void (*)()");
+    }
+    
+    /// `struct Link { Link *next, *prev; }` and `struct Vert { float co[3]; short flag; char pad[2]; Link *link; }`.
+    fn sdna(pointer_size: usize) -> Result<Sdna, UnblendError> {
+        let names = ["*next", "*prev", "co[3]", "flag", "pad[2]", "*link"].map(String::from).to_vec();
+        let types = ["char", "short", "float", "Link", "Vert"].map(String::from).to_vec();
+        let lengths = vec![1, 2, 4, 2 * pointer_size as u16, 16 + pointer_size as u16];
+        let structs = vec![
+            (3, vec![(3, 0), (3, 1)]),
+            (4, vec![(2, 2), (1, 3), (0, 4), (3, 5)]),
+        ];
+        
+        Sdna::new(names, types, lengths, structs, pointer_size)
+    }
+    
+    #[test]
+    fn lay_out_fields() {
+        for pointer_size in [4, 8] {
+            let sdna = sdna(pointer_size).unwrap();
+            
+            let link = sdna.struct_by_name("Link").unwrap();
+            let layout: Vec<_> = link.fields.iter().map(|field| (field.offset, field.size)).collect();
+            assert_eq!(layout, [(0, pointer_size), (pointer_size, pointer_size)]);
+            assert_eq!(link.fields_size(), link.size as usize);
+            
+            let vert = sdna.struct_by_name("Vert").unwrap();
+            let layout: Vec<_> = vert.fields.iter().map(|field| (field.offset, field.size)).collect();
+            assert_eq!(layout, [(0, 12), (12, 2), (14, 2), (16, pointer_size)]);
+            assert_eq!(vert.fields_size(), vert.size as usize);
+            assert_eq!(vert.field_by_name("link").unwrap().type_name, "Link");
+            
+            assert_eq!(sdna.struct_by_type(4).unwrap().sdna, 1);
+            assert!(sdna.struct_by_type(2).is_none());
+        }
+    }
+    
+    #[test]
+    fn reject_fields_too_large() {
+        let names = vec![String::from("a[4294967296][4294967296]")];
+        let types = vec![String::from("char"), String::from("Huge")];
+        let result = Sdna::new(names, types, vec![1, 0], vec![(1, vec![(0, 0)])], 8);
+        assert!(matches!(result, Err(UnblendError::MalformedDna { .. })));
+        
+        let names = vec![String::from("a[x]")];
+        let types = vec![String::from("char"), String::from("Bad")];
+        let result = Sdna::new(names, types, vec![1, 0], vec![(1, vec![(0, 0)])], 8);
+        assert!(matches!(result, Err(UnblendError::MalformedDna { .. })));
+    }
+    
+    #[test]
+    fn report_offset_of_malformed_field() {
+        let blend = crate::blend::read_header(&mut &b"BLENDER-v300"[..]).unwrap();
+        let mut dna1 = crate::blend::encode_dna1(blend.endian, &sdna(8).unwrap());
+        
+        let at = dna1.windows(6).position(|window| window == b"pad[2]").unwrap();
+        dna1[at + 4] = b'x';
+        
+        match crate::blend::read_dna1(&blend, &dna1) {
+            // The third field of `Vert`, i.e. type 0 and name 4.
+            Err(UnblendError::MalformedDna { offset, .. }) => assert_eq!(dna1[offset..offset + 4], [0, 0, 4, 0]),
+            other => panic!("expected MalformedDna, got {other:?}"),
+        }
+    }
+}
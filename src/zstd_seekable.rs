@@ -0,0 +1,276 @@
+//! Random-access reading of zstd-files written in the seekable format.
+//!
+//! See <https://github.com/facebook/zstd/blob/dev/contrib/seekable_format/zstd_seekable_compression_format.md>
+
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::read_ext::ReadExt;
+
+/// Magic number of the skippable frame that holds the seek table.
+const SKIPPABLE_MAGIC: u32 = 0x184D2A5E;
+
+/// Magic number at the very end of the seek table footer.
+const SEEKABLE_MAGIC: u32 = 0x8F92EAB1;
+
+/// Size of the seek table footer: frame count, descriptor and magic.
+const FOOTER_LEN: u64 = 9;
+
+#[derive(Debug, Clone, Copy)]
+pub struct SeekFrame {
+    /// Offset of the frame within the compressed file.
+    pub compressed_offset: u64,
+    pub compressed_size: u32,
+    /// Offset of the frame within the decompressed stream.
+    pub decompressed_offset: u64,
+    pub decompressed_size: u32,
+}
+
+/// Reads the seek table from the end of `read`, then rewinds it.
+///
+/// Returns `None` if the file has no seek table, i.e. is a plain zstd-stream.
+pub fn read_seek_table(read: &mut (impl Read + Seek)) -> std::io::Result<Option<Vec<SeekFrame>>> {
+    let file_len = read.seek(SeekFrom::End(0))?;
+    
+    if file_len < FOOTER_LEN + 8 {
+        read.rewind()?;
+        return Ok(None)
+    }
+    
+    read.seek(SeekFrom::End(-(FOOTER_LEN as i64)))?;
+    let frame_count = u32::from_le_bytes(read.read_exact_array::<4>()?);
+    let descriptor = read.read_byte()?;
+    let magic = u32::from_le_bytes(read.read_exact_array::<4>()?);
+    
+    if magic != SEEKABLE_MAGIC {
+        read.rewind()?;
+        return Ok(None)
+    }
+    
+    let has_checksum = descriptor & 0x80 != 0;
+    let entry_len: u64 = if has_checksum {12} else {8};
+    let table_len = 8 + entry_len * frame_count as u64 + FOOTER_LEN;
+    
+    if table_len > file_len {
+        read.rewind()?;
+        return Ok(None)
+    }
+    
+    read.seek(SeekFrom::End(-(table_len as i64)))?;
+    let skippable_magic = u32::from_le_bytes(read.read_exact_array::<4>()?);
+    let _skippable_size = u32::from_le_bytes(read.read_exact_array::<4>()?);
+    
+    if skippable_magic != SKIPPABLE_MAGIC {
+        read.rewind()?;
+        return Ok(None)
+    }
+    
+    let mut frames = Vec::with_capacity(frame_count as usize);
+    let mut compressed_offset = 0u64;
+    let mut decompressed_offset = 0u64;
+    
+    for _ in 0..frame_count {
+        let compressed_size = u32::from_le_bytes(read.read_exact_array::<4>()?);
+        let decompressed_size = u32::from_le_bytes(read.read_exact_array::<4>()?);
+        
+        if has_checksum {
+            read.read_exact_array::<4>()?;
+        }
+        
+        frames.push(SeekFrame {
+            compressed_offset,
+            compressed_size,
+            decompressed_offset,
+            decompressed_size,
+        });
+        
+        compressed_offset += compressed_size as u64;
+        decompressed_offset += decompressed_size as u64;
+    }
+    
+    read.rewind()?;
+    Ok(Some(frames))
+}
+
+/// A decompressing reader that only ever decompresses the frame it is currently positioned in.
+pub struct SeekableZstd<R: Read + Seek> {
+    inner: R,
+    frames: Vec<SeekFrame>,
+    position: u64,
+    length: u64,
+    /// The most recently decompressed frame, by index.
+    cached: Option<(usize, Vec<u8>)>,
+}
+
+impl<R: Read + Seek> SeekableZstd<R> {
+    pub fn new(inner: R, frames: Vec<SeekFrame>) -> Self {
+        let length = frames.last()
+            .map(|frame| frame.decompressed_offset + frame.decompressed_size as u64)
+            .unwrap_or(0);
+        
+        Self {
+            inner,
+            frames,
+            position: 0,
+            length,
+            cached: None,
+        }
+    }
+    
+    fn frame_at(&self, position: u64) -> usize {
+        self.frames.partition_point(|frame| frame.decompressed_offset + frame.decompressed_size as u64 <= position)
+    }
+    
+    fn load_frame(&mut self, index: usize) -> std::io::Result<&[u8]> {
+        if !matches!(self.cached, Some((cached, _)) if cached == index) {
+            let frame = self.frames[index];
+            self.inner.seek(SeekFrom::Start(frame.compressed_offset))?;
+            let compressed = self.inner.read_exact_buffer(frame.compressed_size as usize)?;
+            let decompressed = zstd::bulk::decompress(&compressed, frame.decompressed_size as usize)?;
+            
+            if decompressed.len() != frame.decompressed_size as usize {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("zstd-frame #{index} decompressed to {} instead of {} byte/s", decompressed.len(), frame.decompressed_size)
+                ));
+            }
+            
+            self.cached = Some((index, decompressed));
+        }
+        
+        Ok(&self.cached.as_ref().unwrap().1)
+    }
+}
+
+impl<R: Read + Seek> Read for SeekableZstd<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.position >= self.length || buf.is_empty() {
+            return Ok(0)
+        }
+        
+        let index = self.frame_at(self.position);
+        let offset = (self.position - self.frames[index].decompressed_offset) as usize;
+        let frame = self.load_frame(index)?;
+        
+        let got = buf.len().min(frame.len() - offset);
+        buf[..got].copy_from_slice(&frame[offset..offset + got]);
+        self.position += got as u64;
+        
+        Ok(got)
+    }
+}
+
+impl<R: Read + Seek> Seek for SeekableZstd<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.length.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        
+        match position {
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            },
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative position"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Compresses every part as its own frame, followed by the seek table.
+    fn seekable(parts: &[&[u8]]) -> Vec<u8> {
+        let mut file = Vec::new();
+        let mut table = Vec::new();
+        
+        for part in parts {
+            let frame = zstd::bulk::compress(part, 0).unwrap();
+            table.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            table.extend_from_slice(&(part.len() as u32).to_le_bytes());
+            file.extend_from_slice(&frame);
+        }
+        
+        table.extend_from_slice(&(parts.len() as u32).to_le_bytes());
+        table.push(0);
+        table.extend_from_slice(&SEEKABLE_MAGIC.to_le_bytes());
+        
+        file.extend_from_slice(&SKIPPABLE_MAGIC.to_le_bytes());
+        file.extend_from_slice(&(table.len() as u32).to_le_bytes());
+        file.extend_from_slice(&table);
+        file
+    }
+    
+    const PARTS: [&[u8]; 3] = [b"BLENDER-v300", b"0123456789", b"abcdefghijklmnopqrstuvwxyz"];
+    
+    fn open() -> SeekableZstd<std::io::Cursor<Vec<u8>>> {
+        let mut file = std::io::Cursor::new(seekable(&PARTS));
+        let frames = read_seek_table(&mut file).unwrap().unwrap();
+        assert_eq!(file.position(), 0);
+        SeekableZstd::new(file, frames)
+    }
+    
+    fn read_at(reader: &mut SeekableZstd<std::io::Cursor<Vec<u8>>>, pos: SeekFrom, len: usize) -> Vec<u8> {
+        reader.seek(pos).unwrap();
+        let mut buf = vec![0; len];
+        reader.read_exact(&mut buf).unwrap();
+        buf
+    }
+    
+    #[test]
+    fn read_seek_table_of_frames() {
+        let file = seekable(&PARTS);
+        let frames = read_seek_table(&mut std::io::Cursor::new(&file)).unwrap().unwrap();
+        
+        let decompressed: Vec<_> = frames.iter().map(|frame| (frame.decompressed_offset, frame.decompressed_size)).collect();
+        assert_eq!(decompressed, [(0, 12), (12, 10), (22, 26)]);
+        
+        for (frame, part) in frames.iter().zip(PARTS) {
+            let start = frame.compressed_offset as usize;
+            let compressed = &file[start..start + frame.compressed_size as usize];
+            assert_eq!(zstd::bulk::decompress(compressed, part.len()).unwrap(), part);
+        }
+    }
+    
+    #[test]
+    fn no_seek_table_in_plain_stream() {
+        let file = zstd::bulk::compress(&PARTS.concat(), 0).unwrap();
+        let mut read = std::io::Cursor::new(file);
+        
+        assert!(read_seek_table(&mut read).unwrap().is_none());
+        assert_eq!(read.position(), 0);
+    }
+    
+    #[test]
+    fn read_whole_stream() {
+        let mut decompressed = Vec::new();
+        open().read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, PARTS.concat());
+    }
+    
+    #[test]
+    fn read_across_frames_and_seek_back() {
+        let mut reader = open();
+        
+        // Spans all three frames, only ever holding the last one.
+        assert_eq!(read_at(&mut reader, SeekFrom::Start(8), 20), b"v3000123456789abcdef");
+        assert_eq!(reader.cached.as_ref().map(|(index, _)| *index), Some(2));
+        
+        assert_eq!(read_at(&mut reader, SeekFrom::Start(0), 7), b"BLENDER");
+        assert_eq!(reader.cached.as_ref().map(|(index, _)| *index), Some(0));
+        
+        assert_eq!(read_at(&mut reader, SeekFrom::Current(5), 3), b"012");
+        assert_eq!(read_at(&mut reader, SeekFrom::End(-3), 3), b"xyz");
+        assert_eq!(read_at(&mut reader, SeekFrom::Current(-28), 2), b"89");
+        
+        // Reading past the end yields nothing, seeking before the start fails.
+        reader.seek(SeekFrom::End(5)).unwrap();
+        assert_eq!(reader.read(&mut [0; 4]).unwrap(), 0);
+        assert!(reader.seek(SeekFrom::Current(-100)).is_err());
+    }
+}
@@ -0,0 +1,43 @@
+//! Streaming the blocks of a file has to agree with indexing it.
+
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use unblend::blend::BlendChunkCode;
+use unblend::{BlendFile, Blocks};
+
+/// The complete blend-files of the fuzzing corpus.
+const CORPUS: [&str; 2] = ["legacy-le64", "large-header"];
+
+fn corpus(name: &str) -> Vec<u8> {
+    std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/read_chunk_header").join(name)).unwrap()
+}
+
+#[test]
+fn walk_blocks() {
+    for name in CORPUS {
+        let data = corpus(name);
+        let index = BlendFile::from_seekable(Cursor::new(&data)).unwrap().index().cloned().unwrap();
+        
+        let mut blocks = Blocks::from_reader(Cursor::new(&data)).unwrap();
+        let mut walked = Vec::new();
+        
+        while let Some(block) = blocks.next_block() {
+            let mut block = block.unwrap();
+            
+            let mut payload = Vec::new();
+            block.data.read_to_end(&mut payload).unwrap();
+            assert_eq!(payload.len() as u64, block.header.size, "{name}: payload of {}", block.header.code);
+            
+            walked.push((block.offset, block.header.code, block.header.size));
+        }
+        
+        let indexed: Vec<_> = index.iter().map(|entry| (entry.offset, entry.header.code, entry.header.size)).collect();
+        assert_eq!(walked, indexed, "{name}");
+        
+        let codes: Vec<_> = walked.iter().map(|(_, code, _)| *code).collect();
+        assert!(codes.contains(&BlendChunkCode(*b"DNA1")), "{name}: no DNA1");
+        assert_eq!(codes.last(), Some(&BlendChunkCode(*b"ENDB")), "{name}: ENDB is not last");
+        assert_eq!(codes.iter().filter(|code| **code == BlendChunkCode(*b"ENDB")).count(), 1, "{name}");
+    }
+}
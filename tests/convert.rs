@@ -0,0 +1,56 @@
+//! Converting a blend-file to another pointer-size and endianness and back has to keep every value.
+
+use std::io::Cursor;
+use std::path::Path;
+
+use unblend::blend::{BlendEndian, BlendHeaderLayout, BlendUsize};
+use unblend::convert::convert;
+use unblend::{BlendDiff, BlendFile, ConvertTarget};
+
+/// The complete blend-files of the fuzzing corpus, all of them `64le`.
+const CORPUS: [&str; 3] = ["legacy-le64", "large-header", "zero-size-self-embedding"];
+
+fn open(data: &[u8]) -> BlendFile<Cursor<&[u8]>> {
+    BlendFile::from_seekable(Cursor::new(data)).unwrap()
+}
+
+fn convert_to(data: &[u8], target: &str) -> Vec<u8> {
+    let mut converted = Vec::new();
+    // Raw blocks keep their bytes both ways, so that they survive the round-trip.
+    convert(&mut open(data), target.parse::<ConvertTarget>().unwrap(), true, &mut converted).unwrap();
+    converted
+}
+
+#[test]
+fn convert_64le_to_32be_and_back() {
+    for name in CORPUS {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/read_chunk_header").join(name);
+        let original = std::fs::read(path).unwrap();
+        
+        let converted = convert_to(&original, "32be");
+        let header = *open(&converted).header();
+        assert_eq!((header.layout, header.usize, header.endian), (BlendHeaderLayout::Legacy, BlendUsize::U32, BlendEndian::BE));
+        
+        let back = convert_to(&converted, "64le");
+        let diff = BlendDiff::build(&mut open(&original), &mut open(&back)).unwrap();
+        
+        assert!(diff.added.is_empty() && diff.removed.is_empty(), "`{name}` lost or gained blocks: {diff:?}");
+        assert!(diff.changed.is_empty(), "`{name}` changed: {diff:?}");
+        // 32-bit files can only have the legacy header, so the large one is lost.
+        assert!(diff.header.iter().all(|change| change.path == "layout"), "`{name}` changed its header: {diff:?}");
+    }
+}
+
+#[test]
+fn refuse_to_swap_raw_blocks() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/read_chunk_header/legacy-le64");
+    let original = std::fs::read(path).unwrap();
+    
+    let mut converted = Vec::new();
+    let result = convert(&mut open(&original), "32be".parse().unwrap(), false, &mut converted);
+    assert!(matches!(result, Err(unblend::UnblendError::UnswappableBlocks(2))), "{result:?}");
+    assert!(converted.is_empty());
+    
+    // Without changing the endianness, there is nothing to swap.
+    convert(&mut open(&original), "32le".parse().unwrap(), false, &mut converted).unwrap();
+}
@@ -0,0 +1,34 @@
+//! Exploding a blend-file and reassembling it has to yield the very same file.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The complete blend-files of the fuzzing corpus.
+const CORPUS: [&str; 2] = ["legacy-le64", "large-header"];
+
+fn corpus(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/read_chunk_header").join(name)
+}
+
+fn unblend(args: &[&Path]) {
+    let output = Command::new(env!("CARGO_BIN_EXE_unblend")).args(args).output().unwrap();
+    assert!(output.status.success(), "unblend {args:?} failed: {}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn explode_then_reblend() {
+    let dir = tempfile::tempdir().unwrap();
+    
+    for name in CORPUS {
+        for format in ["zip", "tar"] {
+            let src = corpus(name);
+            let archive = dir.path().join(format!("{name}.{format}"));
+            let dst = dir.path().join(format!("{name}.{format}.blend"));
+            
+            unblend(&[&src, &archive]);
+            unblend(&[Path::new("reblend"), &archive, &dst]);
+            
+            assert!(std::fs::read(&src).unwrap() == std::fs::read(&dst).unwrap(), "`{name}` changed thru {format}");
+        }
+    }
+}